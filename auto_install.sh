@@ -8,6 +8,7 @@
 set -e
 
 SCRIPT_DIR="$(cd "$(dirname "$0")" && pwd)"
+MULTICALL="cursor-guard"
 BINARIES=("grep" "tail" "head")
 
 echo "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"
@@ -17,15 +18,8 @@ echo ""
 
 # 检查编译产物
 echo "📦 检查编译产物..."
-ALL_EXIST=true
-for bin in "${BINARIES[@]}"; do
-    if [ ! -f "$SCRIPT_DIR/target/release/$bin" ]; then
-        echo "❌ 错误: 找不到 $bin"
-        ALL_EXIST=false
-    fi
-done
-
-if [ "$ALL_EXIST" = false ]; then
+if [ ! -f "$SCRIPT_DIR/target/release/$MULTICALL" ]; then
+    echo "❌ 错误: 找不到 $MULTICALL"
     echo ""
     echo "需要先编译项目："
     echo "  cd $SCRIPT_DIR"
@@ -33,7 +27,7 @@ if [ "$ALL_EXIST" = false ]; then
     echo "  cargo build --release"
     exit 1
 fi
-echo "✅ 所有二进制文件都存在"
+echo "✅ 二进制文件存在"
 echo ""
 
 # 决定安装位置
@@ -54,7 +48,7 @@ echo ""
 # 备份现有文件
 echo "💾 备份现有文件（如果有）..."
 for bin in "${BINARIES[@]}"; do
-    if [ -f "$INSTALL_DIR/$bin" ]; then
+    if [ -f "$INSTALL_DIR/$bin" ] && [ ! -L "$INSTALL_DIR/$bin" ]; then
         BACKUP_FILE="$INSTALL_DIR/${bin}.backup.$(date +%Y%m%d_%H%M%S)"
         mv "$INSTALL_DIR/$bin" "$BACKUP_FILE"
         echo "  • $bin -> $BACKUP_FILE"
@@ -64,10 +58,12 @@ echo ""
 
 # 安装
 echo "📥 安装包装器..."
+cp "$SCRIPT_DIR/target/release/$MULTICALL" "$INSTALL_DIR/$MULTICALL"
+chmod +x "$INSTALL_DIR/$MULTICALL"
+echo "  ✅ $MULTICALL"
 for bin in "${BINARIES[@]}"; do
-    cp "$SCRIPT_DIR/target/release/$bin" "$INSTALL_DIR/$bin"
-    chmod +x "$INSTALL_DIR/$bin"
-    echo "  ✅ $bin"
+    ln -sf "$MULTICALL" "$INSTALL_DIR/$bin"
+    echo "  🔗 $bin -> $MULTICALL"
 done
 echo ""
 
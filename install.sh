@@ -8,6 +8,7 @@ set -e
 
 SCRIPT_DIR="$(cd "$(dirname "$0")" && pwd)"
 INSTALL_DIR="/usr/local/bin"
+MULTICALL="cursor-guard"
 BINARIES=("grep" "tail" "head")
 
 echo "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"
@@ -17,14 +18,12 @@ echo ""
 
 # 检查编译产物是否存在
 echo "📦 检查编译产物..."
-for bin in "${BINARIES[@]}"; do
-    if [ ! -f "$SCRIPT_DIR/target/release/$bin" ]; then
-        echo "❌ 错误: 找不到编译产物: $bin"
-        echo "请先运行: cargo build --release"
-        exit 1
-    fi
-done
-echo "✅ 所有编译产物都存在"
+if [ ! -f "$SCRIPT_DIR/target/release/$MULTICALL" ]; then
+    echo "❌ 错误: 找不到编译产物: $MULTICALL"
+    echo "请先运行: cargo build --release"
+    exit 1
+fi
+echo "✅ 编译产物存在"
 echo ""
 
 # 检查是否需要 sudo
@@ -40,7 +39,7 @@ echo ""
 # 备份原有的包装器（如果存在）
 echo "💾 备份现有文件（如果有）..."
 for bin in "${BINARIES[@]}"; do
-    if [ -f "$INSTALL_DIR/$bin" ]; then
+    if [ -f "$INSTALL_DIR/$bin" ] && [ ! -L "$INSTALL_DIR/$bin" ]; then
         BACKUP_FILE="$INSTALL_DIR/${bin}.backup.$(date +%Y%m%d_%H%M%S)"
         $SUDO mv "$INSTALL_DIR/$bin" "$BACKUP_FILE"
         echo "  • $bin -> $BACKUP_FILE"
@@ -50,10 +49,12 @@ echo ""
 
 # 安装新的二进制文件
 echo "📥 安装新的包装器..."
+$SUDO cp "$SCRIPT_DIR/target/release/$MULTICALL" "$INSTALL_DIR/$MULTICALL"
+$SUDO chmod +x "$INSTALL_DIR/$MULTICALL"
+echo "  ✅ $MULTICALL -> $INSTALL_DIR/$MULTICALL"
 for bin in "${BINARIES[@]}"; do
-    $SUDO cp "$SCRIPT_DIR/target/release/$bin" "$INSTALL_DIR/$bin"
-    $SUDO chmod +x "$INSTALL_DIR/$bin"
-    echo "  ✅ $bin -> $INSTALL_DIR/$bin"
+    $SUDO ln -sf "$MULTICALL" "$INSTALL_DIR/$bin"
+    echo "  🔗 $bin -> $MULTICALL"
 done
 echo ""
 
@@ -0,0 +1,213 @@
+//! AI Agent 检测
+//!
+//! 每个检测器描述一个 Agent 的信号：
+//! - 环境变量：Agent 执行命令时设置、用户手动执行时没有的变量，例如 `CURSOR_AGENT=1`
+//! - 进程名：祖先进程中出现的 Agent 进程，例如 `aider`
+//!
+//! 先按注册顺序检查所有检测器的环境变量，都没有命中时再检查祖先进程。
+//! 配置中的 `[agents.<名称>]` 可以给内置检测器补充信号，或注册新的 Agent。
+//! Copilot（VS Code agent 模式）和 Windsurf 在用户自己的终端里执行命令，
+//! 没有区别于手动执行的信号，所以没有内置检测器，需要在配置中注册。
+
+use crate::procfs::ProcFs;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 内置检测器
+pub const DETECTORS: &[Detector] = &[
+    // 手动在 Cursor 终端执行时进程树和大部分变量都一样，只有 CURSOR_AGENT 能区分
+    Detector { name: "cursor", env: &["CURSOR_AGENT=1"], processes: &[] },
+    Detector { name: "claude-code", env: &["CLAUDECODE=1"], processes: &[] },
+    Detector { name: "codex", env: &["CODEX_SANDBOX", "CODEX_SANDBOX_NETWORK_DISABLED"], processes: &["codex"] },
+    Detector { name: "aider", env: &[], processes: &["aider"] },
+];
+
+/// 一个 Agent 的检测信号
+#[derive(Debug, Clone, Copy)]
+pub struct Detector {
+    /// Agent 名称，也用于 `[profiles.<名称>]` 和保存文件名
+    pub name: &'static str,
+    /// 环境变量信号：`VAR=value` 要求值相等，`VAR` 要求非空
+    pub env: &'static [&'static str],
+    /// 祖先进程名（`comm`）
+    pub processes: &'static [&'static str],
+}
+
+/// 配置中补充的检测信号
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentSignals {
+    /// 环境变量信号，格式同 [`Detector::env`]
+    pub env: Vec<String>,
+    /// 祖先进程名
+    pub processes: Vec<String>,
+}
+
+/// 检测结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detection {
+    /// Agent 名称
+    pub agent: String,
+    /// 命中的信号，例如 `env CURSOR_AGENT=1`、`process aider (pid 123)`
+    pub signal: String,
+}
+
+/// 内置检测器与配置合并后的信号表（按检测顺序）
+fn signal_table(extra: &BTreeMap<String, AgentSignals>) -> Vec<(String, AgentSignals)> {
+    let mut table: Vec<(String, AgentSignals)> = DETECTORS
+        .iter()
+        .map(|d| {
+            let signals = AgentSignals {
+                env: d.env.iter().map(|s| s.to_string()).collect(),
+                processes: d.processes.iter().map(|s| s.to_string()).collect(),
+            };
+            (d.name.to_string(), signals)
+        })
+        .collect();
+    for (name, signals) in extra {
+        match table.iter_mut().find(|(n, _)| n == name) {
+            Some((_, existing)) => {
+                existing.env.extend(signals.env.iter().cloned());
+                existing.processes.extend(signals.processes.iter().cloned());
+            }
+            None => table.push((name.clone(), signals.clone())),
+        }
+    }
+    table
+}
+
+/// 检测当前进程的 Agent
+///
+/// `lookup` 读取环境变量，`proc` 和 `pid` 用于检查祖先进程
+pub fn detect(
+    extra: &BTreeMap<String, AgentSignals>,
+    lookup: impl Fn(&str) -> Option<String>,
+    proc: &ProcFs,
+    pid: u32,
+) -> Option<Detection> {
+    let table = signal_table(extra);
+
+    for (name, signals) in &table {
+        if let Some(signal) = signals.env.iter().find(|s| env_matches(s, &lookup)) {
+            return Some(Detection { agent: name.clone(), signal: format!("env {}", signal) });
+        }
+    }
+
+    if table.iter().all(|(_, signals)| signals.processes.is_empty()) {
+        return None;
+    }
+    let ancestors: Vec<(u32, String)> = proc
+        .ancestors(pid)
+        .into_iter()
+        .filter_map(|p| Some((p, proc.comm(p)?)))
+        .collect();
+    for (name, signals) in &table {
+        for (p, comm) in &ancestors {
+            if signals.processes.iter().any(|s| s == comm) {
+                return Some(Detection { agent: name.clone(), signal: format!("process {} (pid {})", comm, p) });
+            }
+        }
+    }
+    None
+}
+
+/// 逐条检查所有信号（`cursor-guard explain` 展示用），返回 (Agent, 信号, 是否命中)
+pub fn checks(
+    extra: &BTreeMap<String, AgentSignals>,
+    lookup: impl Fn(&str) -> Option<String>,
+    proc: &ProcFs,
+    pid: u32,
+) -> Vec<(String, String, bool)> {
+    let comms: Vec<String> = proc.ancestors(pid).into_iter().filter_map(|p| proc.comm(p)).collect();
+    let mut result = Vec::new();
+    for (name, signals) in signal_table(extra) {
+        for signal in &signals.env {
+            result.push((name.clone(), format!("env {}", signal), env_matches(signal, &lookup)));
+        }
+        for process in &signals.processes {
+            result.push((name.clone(), format!("process {}", process), comms.contains(process)));
+        }
+    }
+    result
+}
+
+/// `VAR=value` 要求值相等，`VAR` 要求非空
+fn env_matches(signal: &str, lookup: &impl Fn(&str) -> Option<String>) -> bool {
+    match signal.split_once('=') {
+        Some((var, expected)) => lookup(var).as_deref() == Some(expected),
+        None => lookup(signal).is_some_and(|value| !value.is_empty()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procfs::fake::FakeProc;
+
+    fn env_of(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+    }
+
+    fn no_proc() -> ProcFs {
+        ProcFs::new("/tmp/cursor_test_procfs/none")
+    }
+
+    #[test]
+    fn test_env_detectors() {
+        let none = BTreeMap::new();
+        let cursor = detect(&none, env_of(&[("CURSOR_AGENT", "1")]), &no_proc(), 1).unwrap();
+        assert_eq!(cursor, Detection { agent: "cursor".into(), signal: "env CURSOR_AGENT=1".into() });
+
+        let claude = detect(&none, env_of(&[("CLAUDECODE", "1")]), &no_proc(), 1).unwrap();
+        assert_eq!(claude.agent, "claude-code");
+
+        let codex = detect(&none, env_of(&[("CODEX_SANDBOX", "seatbelt")]), &no_proc(), 1).unwrap();
+        assert_eq!(codex.agent, "codex");
+    }
+
+    #[test]
+    fn test_manual_terminal_is_not_an_agent() {
+        let vars = &[("CURSOR_AGENT", "0"), ("TERM_PROGRAM", "vscode"), ("CODEX_SANDBOX", "")];
+        assert_eq!(detect(&BTreeMap::new(), env_of(vars), &no_proc(), 1), None);
+    }
+
+    #[test]
+    fn test_process_detector() {
+        let fake = FakeProc::new("agent_detect");
+        fake.process(1, 0, "init").process(10, 1, "aider").process(20, 10, "bash").process(30, 20, "grep");
+        let found = detect(&BTreeMap::new(), env_of(&[]), &ProcFs::new(&fake.root), 30).unwrap();
+        assert_eq!(found, Detection { agent: "aider".into(), signal: "process aider (pid 10)".into() });
+    }
+
+    #[test]
+    fn test_configured_signals() {
+        let mut extra = BTreeMap::new();
+        extra.insert(
+            "windsurf".to_string(),
+            AgentSignals { env: vec!["WINDSURF_CASCADE=1".into()], processes: Vec::new() },
+        );
+        extra.insert("my-agent".to_string(), AgentSignals { env: vec!["MY_AGENT".into()], processes: Vec::new() });
+
+        let windsurf = detect(&extra, env_of(&[("WINDSURF_CASCADE", "1")]), &no_proc(), 1).unwrap();
+        assert_eq!(windsurf.agent, "windsurf");
+        let custom = detect(&extra, env_of(&[("MY_AGENT", "yes")]), &no_proc(), 1).unwrap();
+        assert_eq!(custom.agent, "my-agent");
+    }
+
+    #[test]
+    fn test_checks_list_every_signal() {
+        let checks = checks(&BTreeMap::new(), env_of(&[("CLAUDECODE", "1")]), &no_proc(), 1);
+        assert!(checks.contains(&("claude-code".to_string(), "env CLAUDECODE=1".to_string(), true)));
+        assert!(checks.contains(&("cursor".to_string(), "env CURSOR_AGENT=1".to_string(), false)));
+        assert!(checks.contains(&("aider".to_string(), "process aider".to_string(), false)));
+    }
+
+    #[test]
+    fn test_env_wins_over_process() {
+        let fake = FakeProc::new("agent_env_first");
+        fake.process(1, 0, "init").process(10, 1, "aider").process(20, 10, "grep");
+        let found = detect(&BTreeMap::new(), env_of(&[("CLAUDECODE", "1")]), &ProcFs::new(&fake.root), 20).unwrap();
+        assert_eq!(found.agent, "claude-code");
+    }
+}
@@ -0,0 +1,192 @@
+//! 审计日志
+//!
+//! Agent 的每次调用追加一条 JSON 记录（JSON Lines），用来调整阈值、回顾 Agent 做过什么。
+//! 默认位于状态目录下的 `audit.jsonl`，可以用 `[audit] path` 修改。
+//! 写入时对文件加排他锁（flock），并发执行的命令不会交错写入。
+//! 日志超过 `[audit] max_bytes` 时改名为 `<path>.1` 后重新开始，只保留一份旧日志。
+
+use crate::config::AuditConfig;
+use crate::mode::Mode;
+use crate::policy::Enforcement;
+use crate::{lock_exclusive, session};
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// 默认的审计日志文件名（位于状态目录中）
+pub const AUDIT_LOG_NAME: &str = "audit.jsonl";
+
+/// 一次调用的记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// 开始时间（RFC 3339）
+    pub timestamp: String,
+    /// 会话标识
+    pub session: String,
+    /// 工作目录
+    pub cwd: String,
+    /// 检测到的 Agent
+    pub agent: Option<String>,
+    /// 包装的命令名
+    pub command: String,
+    /// 强制模式（已合并全局设置）
+    pub mode: Mode,
+    /// 执行方式：passthrough、refuse、refuse-repeat（拒绝重复命令）、follow、capture、run、
+    /// replay（结果缓存命中，直接输出缓存的结果）；重复命令照常执行时记为 run
+    pub action: String,
+    /// 绕过来源：`--cg-raw` 或 `CURSOR_GUARD_BYPASS`
+    pub bypass: Option<String>,
+    /// 用户输入的命令行（含命令名）
+    pub argv: Vec<String>,
+    /// 实际执行的命令行，strict 模式拒绝执行时为 null
+    pub rewritten: Option<Vec<String>>,
+    /// 匹配的规则（无论是否生效）
+    pub rules: Vec<Enforcement>,
+    /// 规则是否生效（管道中的命令、绕过时为 false）
+    pub rules_applied: bool,
+    /// 保存的管道输入文件
+    pub capture: Option<String>,
+    /// 管道输入的字节数
+    pub input_bytes: Option<u64>,
+    /// 管道输入的行数
+    pub input_lines: Option<u64>,
+    /// 退出码
+    pub exit_code: i32,
+    /// 耗时（毫秒）
+    pub duration_ms: u64,
+}
+
+/// 审计日志路径
+pub fn log_path(config: &AuditConfig) -> PathBuf {
+    if config.path.is_empty() {
+        session::state_dir().join(AUDIT_LOG_NAME)
+    } else {
+        PathBuf::from(&config.path)
+    }
+}
+
+/// 轮转后的旧日志：`<path>.1`
+pub fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// 追加一条记录；max_bytes 不为 0 且写入后会超过该大小时，先把现有日志轮转为 `<path>.1`
+pub fn append(path: &Path, record: &Record, max_bytes: u64) -> io::Result<()> {
+    let mut line = serde_json::to_string(record).map_err(io::Error::other)?;
+    line.push('\n');
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    loop {
+        let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+        lock_exclusive(&file)?;
+        // 等锁期间其他进程可能已经轮转了日志，打开的文件不再是 path 时重新打开
+        let meta = file.metadata()?;
+        let current = fs::metadata(path)?;
+        if (meta.dev(), meta.ino()) != (current.dev(), current.ino()) {
+            continue;
+        }
+        if max_bytes > 0 && meta.len() > 0 && meta.len() + line.len() as u64 > max_bytes {
+            fs::rename(path, rotated_path(path))?;
+            continue;
+        }
+        return file.write_all(line.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn record(command: &str) -> Record {
+        Record {
+            timestamp: "2026-01-01T00:00:00.000+08:00".to_string(),
+            session: "1-1001".to_string(),
+            cwd: "/repo".to_string(),
+            agent: Some("cursor".to_string()),
+            command: command.to_string(),
+            mode: Mode::Enforce,
+            action: "run".to_string(),
+            bypass: None,
+            argv: vec![command.to_string(), "-n".to_string(), "5".to_string()],
+            rewritten: Some(vec![command.to_string(), "-n".to_string(), "100".to_string()]),
+            rules: vec![Enforcement::new("tail.min_lines", "tail -n 5", "tail -n 100")],
+            rules_applied: true,
+            capture: None,
+            input_bytes: None,
+            input_lines: None,
+            exit_code: 0,
+            duration_ms: 3,
+        }
+    }
+
+    #[test]
+    fn test_record_fields() {
+        let json: serde_json::Value = serde_json::to_value(record("tail")).unwrap();
+        assert_eq!(json["mode"], "enforce");
+        assert_eq!(json["rules"][0]["rule"], "tail.min_lines");
+        assert_eq!(json["rewritten"][2], "100");
+        assert!(json["capture"].is_null());
+    }
+
+    #[test]
+    fn test_concurrent_appends_do_not_interleave() {
+        let path = std::env::temp_dir().join(format!("cursor_test_audit_{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    for _ in 0..20 {
+                        append(&path, &record(&format!("cmd{}", i)), 0).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let content = fs::read_to_string(&path).unwrap();
+        let records: Vec<Record> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(records.len(), 160);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_rotation_keeps_one_old_log() {
+        let path = std::env::temp_dir().join(format!("cursor_test_audit_rotate_{}.jsonl", std::process::id()));
+        let rotated = rotated_path(&path);
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(&rotated);
+        let size = serde_json::to_string(&record("tail")).unwrap().len() as u64 + 1;
+
+        // 每个文件最多放 3 条记录，写入 7 条后当前日志 1 条、旧日志 3 条
+        for _ in 0..7 {
+            append(&path, &record("tail"), size * 3).unwrap();
+        }
+        assert!(rotated.to_string_lossy().ends_with(".jsonl.1"));
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+        assert_eq!(fs::read_to_string(&rotated).unwrap().lines().count(), 3);
+
+        // 单条记录超过上限时仍然写入
+        append(&path, &record("tail"), 1).unwrap();
+        append(&path, &record("tail"), 1).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(&rotated);
+    }
+
+    #[test]
+    fn test_log_path_override() {
+        let config = AuditConfig { path: "/var/log/cg.jsonl".to_string(), ..AuditConfig::default() };
+        assert_eq!(log_path(&config), PathBuf::from("/var/log/cg.jsonl"));
+        assert!(log_path(&AuditConfig::default()).ends_with("cursor-guard/audit.jsonl"));
+    }
+}
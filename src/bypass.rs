@@ -0,0 +1,99 @@
+//! 绕过强制规则
+//!
+//! 确实只需要少量输出时（例如 `head -1 VERSION`），可以临时跳过包装器：
+//! - 单次调用：参数中加 `--cg-raw`，执行前会被去掉
+//! - 整个会话：`export CURSOR_GUARD_BYPASS=1`（所有命令）或 `CURSOR_GUARD_BYPASS=head,tail`（指定命令）
+//!
+//! 每次绕过都会记录到审计日志中。
+
+use std::fmt;
+
+/// 单次调用的绕过标记
+pub const RAW_FLAG: &str = "--cg-raw";
+
+/// 会话级绕过的环境变量
+pub const BYPASS_ENV: &str = "CURSOR_GUARD_BYPASS";
+
+/// 绕过的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bypass {
+    /// 参数中的 `--cg-raw`
+    Flag,
+    /// `CURSOR_GUARD_BYPASS` 环境变量
+    Env,
+}
+
+impl fmt::Display for Bypass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bypass::Flag => f.write_str(RAW_FLAG),
+            Bypass::Env => f.write_str(BYPASS_ENV),
+        }
+    }
+}
+
+/// 去掉 `--` 之前的所有 `--cg-raw`，返回剩余参数和是否出现过该标记
+///
+/// `--` 之后的参数原样保留，要搜索字面的 `--cg-raw` 时写作 `grep -- --cg-raw`
+pub fn strip_flag(args: Vec<String>) -> (Vec<String>, bool) {
+    let mut found = false;
+    let mut options_ended = false;
+    let mut kept = Vec::with_capacity(args.len());
+    for arg in args {
+        if !options_ended && arg == RAW_FLAG {
+            found = true;
+            continue;
+        }
+        if arg == "--" {
+            options_ended = true;
+        }
+        kept.push(arg);
+    }
+    (kept, found)
+}
+
+/// `CURSOR_GUARD_BYPASS` 是否覆盖指定命令
+///
+/// `1`、`true`、`yes`、`all` 覆盖所有命令；否则按逗号分隔的命令名匹配
+pub fn env_covers(value: Option<&str>, name: &str) -> bool {
+    let Some(value) = value else {
+        return false;
+    };
+    let value = value.trim().to_ascii_lowercase();
+    if matches!(value.as_str(), "1" | "true" | "yes" | "all") {
+        return true;
+    }
+    value.split(',').any(|item| item.trim() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_strip_flag() {
+        assert_eq!(strip_flag(args(&["--cg-raw", "-1", "VERSION"])), (args(&["-1", "VERSION"]), true));
+        assert_eq!(strip_flag(args(&["-n", "1", "a", "--cg-raw"])), (args(&["-n", "1", "a"]), true));
+        assert_eq!(strip_flag(args(&["-n", "1", "a"])), (args(&["-n", "1", "a"]), false));
+    }
+
+    #[test]
+    fn test_flag_after_double_dash_is_kept() {
+        assert_eq!(strip_flag(args(&["--", "--cg-raw", "a"])), (args(&["--", "--cg-raw", "a"]), false));
+    }
+
+    #[test]
+    fn test_env_covers() {
+        assert!(env_covers(Some("1"), "grep"));
+        assert!(env_covers(Some("ALL"), "tail"));
+        assert!(env_covers(Some("head, tail"), "tail"));
+        assert!(!env_covers(Some("head,tail"), "grep"));
+        assert!(!env_covers(Some("0"), "grep"));
+        assert!(!env_covers(Some(""), "grep"));
+        assert!(!env_covers(None, "grep"));
+    }
+}
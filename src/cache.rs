@@ -0,0 +1,259 @@
+//! 结果缓存（默认关闭）
+//!
+//! Agent 经常在几秒内重复运行同一条 `grep`/`tail`/`head`。文件没有变化时结果也不会变化，
+//! 可以直接用上一次的输出回答，不再重新读取文件。
+//!
+//! 缓存键与重复命令检测相同：改写后的参数、工作目录，以及每个读取的文件（文件参数和 `grep -f`
+//! 等选项读取的文件）的大小和修改时间，文件一旦变化就不会命中。
+//! 只缓存读取的文件全部是普通文件的调用（目录和标准输入无法可靠判断是否变化），
+//! 只缓存退出码为 0 的标准输出。输出到终端且会着色（`grep --color=auto`）的调用不缓存：
+//! 缓存通过管道读取输出，原始命令会因此不再着色。
+//!
+//! 缓存位于状态目录下的 `cache/<键>.out`，元数据在 `cache/<键>.json`。
+
+use crate::config::CacheConfig;
+use crate::{guard, session};
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+/// 读取原始命令输出时的缓冲区大小
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// 默认的缓存目录
+pub fn dir() -> PathBuf {
+    session::state_dir().join("cache")
+}
+
+/// 缓存条目的元数据
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Meta {
+    /// 保存时间（Unix 秒）
+    pub time: i64,
+    /// 展示用的命令行
+    pub command: String,
+    /// 原始命令的退出码
+    pub exit_code: i32,
+    /// 输出字节数
+    pub bytes: u64,
+}
+
+/// 读取的所有文件（文件参数和选项读取的文件）都是普通文件时才能缓存
+pub fn cacheable_files(files: &[&str]) -> bool {
+    !files.is_empty() && files.iter().all(|file| fs::metadata(file).is_ok_and(|meta| meta.is_file()))
+}
+
+/// 结果缓存目录
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: &Path) -> Self {
+        Cache { dir: dir.to_path_buf() }
+    }
+
+    fn output_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.out", key))
+    }
+
+    fn meta_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// 查找未过期的缓存
+    pub fn lookup(&self, key: &str, now: i64, config: &CacheConfig) -> Option<Meta> {
+        let meta: Meta = serde_json::from_str(&fs::read_to_string(self.meta_path(key)).ok()?).ok()?;
+        if now - meta.time > config.max_age_secs as i64 {
+            return None;
+        }
+        let size = fs::metadata(self.output_path(key)).ok()?.len();
+        (size == meta.bytes).then_some(meta)
+    }
+
+    /// 把缓存的输出写到 out
+    pub fn replay(&self, key: &str, out: &mut impl Write) -> io::Result<u64> {
+        let mut file = File::open(self.output_path(key))?;
+        let copied = io::copy(&mut file, out)?;
+        out.flush()?;
+        Ok(copied)
+    }
+
+    /// 执行原始命令，输出照常写到标准输出，同时保存到缓存；name 是包装命令名（写入重入标记）
+    ///
+    /// 输出超过 max_bytes、退出码不为 0 或标准输出被关闭时不保存
+    pub fn run_and_store(
+        &self,
+        key: &str,
+        original: &Path,
+        name: &str,
+        args: &[String],
+        command: &str,
+        config: &CacheConfig,
+    ) -> io::Result<i32> {
+        let mut cmd = Command::new(original);
+        guard::mark_child(&mut cmd, name);
+        cmd.args(args).stdout(Stdio::piped());
+        let mut child = cmd.spawn()?;
+        let Some(mut output) = child.stdout.take() else {
+            return Err(io::Error::other("无法读取原始命令的输出"));
+        };
+
+        // 缓存目录不可用时照常输出，只是不保存
+        let tmp = self.dir.join(format!("{}.{}.tmp", key, process::id()));
+        let mut store = fs::create_dir_all(&self.dir).and_then(|_| File::create(&tmp)).ok();
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        let mut buffer = vec![0u8; BUFFER_SIZE];
+        let mut bytes = 0u64;
+        let mut complete = true;
+
+        loop {
+            let n = match output.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    complete = false;
+                    break;
+                }
+            };
+            let chunk = &buffer[..n];
+            // 下游已关闭（例如 `| head`），不再读取，让原始命令收到 SIGPIPE
+            if out.write_all(chunk).is_err() {
+                complete = false;
+                break;
+            }
+            bytes += n as u64;
+            if bytes > config.max_bytes {
+                store = None;
+            }
+            if let Some(file) = store.as_mut() {
+                if file.write_all(chunk).is_err() {
+                    store = None;
+                }
+            }
+        }
+        let _ = out.flush();
+        drop(output);
+        let exit_code = child.wait()?.code().unwrap_or(1);
+
+        match store {
+            Some(file) if complete && exit_code == 0 => {
+                drop(file);
+                let meta = Meta { time: Local::now().timestamp(), command: command.to_string(), exit_code, bytes };
+                if let Err(e) = self.commit(key, &tmp, &meta) {
+                    eprintln!("⚠️  无法保存结果缓存: {}", e);
+                }
+                self.prune(meta.time, config);
+            }
+            _ => {
+                let _ = fs::remove_file(&tmp);
+            }
+        }
+        Ok(exit_code)
+    }
+
+    /// 先放好输出再写元数据，读取方看到元数据时输出一定完整
+    fn commit(&self, key: &str, tmp: &Path, meta: &Meta) -> io::Result<()> {
+        fs::rename(tmp, self.output_path(key))?;
+        let meta_tmp = self.dir.join(format!("{}.{}.json.tmp", key, process::id()));
+        fs::write(&meta_tmp, serde_json::to_string(meta).map_err(io::Error::other)?)?;
+        fs::rename(&meta_tmp, self.meta_path(key))
+    }
+
+    /// 删除过期的缓存
+    fn prune(&self, now: i64, config: &CacheConfig) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(key) = path.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_suffix(".json")) else {
+                continue;
+            };
+            if self.lookup(key, now, config).is_none() {
+                let _ = fs::remove_file(self.output_path(key));
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+}
+
+/// 打印缓存命中提示（统一格式）
+pub fn print_replay_message(meta: &Meta, now: i64) {
+    let captured_at = Local
+        .timestamp_opt(meta.time, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| meta.time.to_string());
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    eprintln!("📦 [Cursor 强制最佳实践] 结果来自缓存，保存于 {}（{} 秒前）: {}", captured_at, now - meta.time, meta.command);
+    eprintln!("💡 文件没有变化，结果与重新运行相同；确实需要重新运行时加 --cg-raw");
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CacheConfig {
+        CacheConfig { enabled: true, max_age_secs: 300, max_bytes: 1024 }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cursor_test_cache_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_store_and_replay() {
+        let dir = test_dir("replay");
+        let cache = Cache::new(&dir);
+        let args = vec!["cached output".to_string()];
+        let code = cache.run_and_store("k", Path::new("echo"), "echo", &args, "echo cached output", &config()).unwrap();
+        assert_eq!(code, 0);
+
+        let now = Local::now().timestamp();
+        let meta = cache.lookup("k", now, &config()).unwrap();
+        assert_eq!((meta.exit_code, meta.bytes), (0, 14));
+        let mut replayed = Vec::new();
+        cache.replay("k", &mut replayed).unwrap();
+        assert_eq!(replayed, b"cached output\n");
+
+        // 过期后不再命中
+        assert_eq!(cache.lookup("k", now + 301, &config()), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_failures_and_large_output_are_not_cached() {
+        let dir = test_dir("skip");
+        let cache = Cache::new(&dir);
+        let now = Local::now().timestamp();
+        assert_eq!(cache.run_and_store("fail", Path::new("false"), "false", &[], "false", &config()).unwrap(), 1);
+        assert_eq!(cache.lookup("fail", now, &config()), None);
+
+        let small = CacheConfig { max_bytes: 4, ..config() };
+        let args = vec!["too long".to_string()];
+        cache.run_and_store("big", Path::new("echo"), "echo", &args, "echo too long", &small).unwrap();
+        assert_eq!(cache.lookup("big", now, &small), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_cacheable_files() {
+        let file = std::env::temp_dir().join(format!("cursor_test_cacheable_{}.txt", process::id()));
+        fs::write(&file, "x").unwrap();
+        let file_name = file.to_str().unwrap();
+        assert!(cacheable_files(&[file_name]));
+        assert!(!cacheable_files(&[file_name, "/tmp"]));
+        assert!(!cacheable_files(&[]));
+        let _ = fs::remove_file(&file);
+    }
+}
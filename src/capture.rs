@@ -0,0 +1,1163 @@
+//! 保存文件的元数据
+//!
+//! 保存文件名只有 `前缀_pid_时间_随机数.txt`，看不出是哪条命令的输出。
+//! 每个保存文件旁边写一个同名的 `.meta.json`，同时向保存目录的 `index.jsonl` 追加一行，记录：
+//! - 完整的管道命令：通过 `/proc` 读取上层 shell 的 `-c` 参数，以及向管道写入的上游进程
+//! - 工作目录、Agent 和会话标识
+//! - 字节数、行数和内容的 SHA-256
+//!
+//! 之后按命令就能找到"那次 cargo build 的输出"，不用猜文件名（见 `cursor-guard captures`）。
+//!
+//! 管道输入按内容存放在 `objects/<SHA-256>`，每次调用的文件名是指向它的符号链接，
+//! 相同的输入（`cat big.log | grep a` 之后 `cat big.log | grep b`）只占一份磁盘空间。
+//! 没有链接引用的内容在清理时删除。
+//!
+//! 保存目录按 `[capture]` 中的上限自动清理：超过保留时间、会话文件数或总大小时先删除最早的文件。
+//! 每次保存之后顺带清理一次，也可以用 `cursor-guard captures gc` 手动清理。
+//!
+//! 清理时顺带把保存超过 `compress_after_secs` 或大于 `compress_above_bytes` 的内容用 gzip 压缩为
+//! `objects/<SHA-256>.gz`，文件名改为 `<原文件名>.gz`，`.txt` 结尾的文件始终是纯文本。
+//! `cursor-guard captures show/grep` 直接读取压缩内容，包装的 grep/head/tail 读到压缩的保存文件
+//! （或按原来的 `.txt` 文件名读取）时先解压到临时文件（见 [`extract_args`]），原始命令拿到的始终是普通文件。
+
+use crate::config::CaptureConfig;
+use crate::procfs::ProcFs;
+use crate::{config, is_locked, lock_exclusive, pipeline, session};
+
+use chrono::{Local, SecondsFormat};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::CString;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 保存文件的扩展名
+pub const CAPTURE_EXTENSION: &str = "txt";
+
+/// 索引文件名（位于保存目录中）
+pub const INDEX_NAME: &str = "index.jsonl";
+
+/// 按内容存放的子目录
+pub const OBJECTS_DIR: &str = "objects";
+
+/// 压缩后内容的扩展名（`objects/<SHA-256>.gz`）
+pub const COMPRESSED_EXTENSION: &str = "gz";
+
+/// 新存入的内容在这段时间内不会因为没有链接而被删除（存入和创建链接之间不是原子操作）
+const OBJECT_GRACE_SECS: u64 = 60;
+
+/// 元数据文件的扩展名，替换保存文件的 `.txt`
+pub const META_EXTENSION: &str = "meta.json";
+
+/// 向上查找 shell 的最大层数，再往上的 shell 多半是 Agent 自己的启动脚本
+const MAX_SHELL_DEPTH: usize = 4;
+
+/// 一个保存文件的元数据
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureMeta {
+    /// 保存文件路径（压缩后实际的文件名多出 `.gz`）
+    pub path: String,
+    /// 保存时间（RFC 3339）
+    pub created: String,
+    /// 包装的命令，例如 `tail -n 100`
+    pub command: String,
+    /// 所在的完整命令（上层 shell 的 `-c` 参数），例如 `cargo build 2>&1 | tail -n 100`
+    pub pipeline: Option<String>,
+    /// 向管道写入的上游进程的命令行
+    pub upstream: Vec<String>,
+    /// 工作目录
+    pub cwd: String,
+    /// 检测到的 Agent
+    pub agent: Option<String>,
+    /// 会话标识
+    pub session: String,
+    /// 字节数
+    pub bytes: u64,
+    /// 行数
+    pub lines: u64,
+    /// 内容的 SHA-256
+    pub sha256: String,
+}
+
+/// 管道输入的来源
+///
+/// 上游进程可能在输入读完后立即退出，需要在开始读取之前收集
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Origin {
+    /// 上层 shell 执行的完整命令
+    pub pipeline: Option<String>,
+    /// 上游进程的命令行
+    pub upstream: Vec<String>,
+}
+
+impl Origin {
+    /// 收集当前进程标准输入的来源，管道写端来自本次调用共享的 `/proc` 扫描
+    pub fn detect() -> Origin {
+        origin_of(&ProcFs::system(), process::id(), pipeline::pipes())
+    }
+}
+
+/// 根据进程树和管道写端收集来源
+fn origin_of(proc: &ProcFs, pid: u32, pipes: &pipeline::Pipes) -> Origin {
+    let ancestors = proc.ancestors(pid);
+    // 只看最近的 shell：管道中的命令由它 fork 出来
+    let pipeline = ancestors
+        .iter()
+        .take(MAX_SHELL_DEPTH)
+        .find(|&&ancestor| proc.comm(ancestor).is_some_and(|comm| pipeline::is_shell(&comm)))
+        .and_then(|&shell| proc.argv(shell))
+        .and_then(|argv| shell_script(&argv));
+    let upstream = pipes
+        .stdin
+        .map(|inode| pipes.ends.writers(inode))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|writer| *writer != pid && !ancestors.contains(writer))
+        .filter_map(|writer| proc.cmdline(writer))
+        .collect();
+    Origin { pipeline, upstream }
+}
+
+/// shell 命令行中 `-c` 之后的脚本，例如 `bash -lc 'cargo build | tail'`、`bash -c -l 'script'`
+fn shell_script(argv: &[String]) -> Option<String> {
+    let is_option = |arg: &str| arg.starts_with('-') && arg != "-" && arg != "--";
+    let flag = argv.iter().skip(1).position(|arg| {
+        is_option(arg) && !arg.starts_with("--") && arg[1..].chars().all(|c| c.is_ascii_alphabetic()) && arg.contains('c')
+    })?;
+    argv.iter().skip(flag + 2).find(|arg| !is_option(arg) && arg.as_str() != "--").cloned()
+}
+
+/// 保存文件对应的元数据文件
+pub fn meta_path(capture: &Path) -> PathBuf {
+    saved_path(capture).with_extension(META_EXTENSION)
+}
+
+/// 保存时的文件名：压缩后的文件去掉 `.gz`
+pub fn saved_path(capture: &Path) -> PathBuf {
+    match capture.extension() {
+        Some(ext) if ext == COMPRESSED_EXTENSION => capture.with_extension(""),
+        _ => capture.to_path_buf(),
+    }
+}
+
+/// 文件名是否是保存文件（`.txt` 或压缩后的 `.txt.gz`）
+fn is_capture_name(path: &Path) -> bool {
+    saved_path(path).extension().is_some_and(|ext| ext == CAPTURE_EXTENSION)
+}
+
+/// 保存目录中的索引文件
+pub fn index_path(dir: &Path) -> PathBuf {
+    dir.join(INDEX_NAME)
+}
+
+/// 读取保存文件，生成元数据
+pub fn describe(capture: &Path, origin: &Origin, command: &str) -> io::Result<CaptureMeta> {
+    let (bytes, lines, sha256) = digest(capture)?;
+    Ok(CaptureMeta {
+        path: capture.display().to_string(),
+        created: Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+        command: command.to_string(),
+        pipeline: origin.pipeline.clone(),
+        upstream: origin.upstream.clone(),
+        cwd: env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default(),
+        agent: config::global().agent.as_ref().map(|found| found.agent.clone()),
+        session: session::id(),
+        bytes,
+        lines,
+        sha256,
+    })
+}
+
+/// 文件的字节数、行数和 SHA-256
+fn digest(capture: &Path) -> io::Result<(u64, u64, String)> {
+    let mut file = File::open(capture)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let (mut bytes, mut lines) = (0u64, 0u64);
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        let chunk = &buffer[..n];
+        hasher.update(chunk);
+        bytes += n as u64;
+        lines += chunk.iter().filter(|&&b| b == b'\n').count() as u64;
+    }
+    Ok((bytes, lines, format!("{:x}", hasher.finalize())))
+}
+
+/// 写元数据文件，并向索引追加一行
+pub fn write(meta: &CaptureMeta, index: &Path) -> io::Result<()> {
+    let pretty = serde_json::to_string_pretty(meta).map_err(io::Error::other)?;
+    fs::write(meta_path(Path::new(&meta.path)), pretty + "\n")?;
+
+    let mut line = serde_json::to_string(meta).map_err(io::Error::other)?;
+    line.push('\n');
+    let mut file = OpenOptions::new().append(true).create(true).open(index)?;
+    lock_exclusive(&file)?;
+    file.write_all(line.as_bytes())
+}
+
+/// 生成并写入保存文件的元数据（索引位于配置的保存目录）
+pub fn record(capture: &Path, origin: &Origin, command: &str) -> io::Result<CaptureMeta> {
+    let meta = describe(capture, origin, command)?;
+    write(&meta, &index_path(Path::new(&config::global().capture.dir)))?;
+    Ok(meta)
+}
+
+/// 内容不再变化后重新统计并写入元数据（跟随模式的后台记录结束时），来源等信息沿用 meta
+pub fn finish(meta: CaptureMeta) -> io::Result<CaptureMeta> {
+    let (bytes, lines, sha256) = digest(Path::new(&meta.path))?;
+    let meta = CaptureMeta { bytes, lines, sha256, ..meta };
+    write(&meta, &index_path(Path::new(&config::global().capture.dir)))?;
+    Ok(meta)
+}
+
+/// 保存目录中的一个保存文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    pub path: PathBuf,
+    /// 最后修改时间（Unix 秒），链接取链接本身的创建时间
+    pub modified: i64,
+    /// 内容大小
+    pub bytes: u64,
+    /// 链接指向的内容，普通文件为 None
+    pub object: Option<PathBuf>,
+    /// 元数据，旧版本保存的文件或元数据损坏时为 None
+    pub meta: Option<CaptureMeta>,
+    /// 跟随模式仍在追加内容（文件被加了锁），清理时不删除也不压缩
+    pub recording: bool,
+}
+
+impl Capture {
+    /// 展示用的命令：优先完整管道命令，没有元数据时取文件名中的前缀
+    pub fn command(&self) -> String {
+        match &self.meta {
+            Some(meta) => meta.pipeline.clone().unwrap_or_else(|| meta.command.clone()),
+            None => {
+                let stem = self.path.file_stem().and_then(|n| n.to_str()).unwrap_or_default();
+                // 文件名：前缀_pid_日期_时间_随机数
+                let parts: Vec<&str> = stem.rsplitn(5, '_').collect();
+                parts.last().copied().unwrap_or(stem).to_string()
+            }
+        }
+    }
+
+    /// 会话标识
+    pub fn session(&self) -> Option<&str> {
+        self.meta.as_ref().map(|meta| meta.session.as_str())
+    }
+
+    /// 实际占用磁盘的文件，指向同一内容的链接共用一份
+    pub fn storage(&self) -> &Path {
+        self.object.as_deref().unwrap_or(&self.path)
+    }
+
+    /// 内容是否已压缩
+    pub fn compressed(&self) -> bool {
+        self.object.as_deref().is_some_and(is_compressed_object)
+    }
+}
+
+/// `objects/` 中压缩过的内容
+fn is_compressed_object(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == COMPRESSED_EXTENSION)
+        && path.parent().and_then(Path::file_name).is_some_and(|name| name == OBJECTS_DIR)
+}
+
+/// path 是否是保存目录 dir 中内容已压缩的保存文件：位于 dir 中、链接到 `dir/objects/*.gz`
+///
+/// 其他位置的 `objects/*.gz`（例如项目自己的文件）不算
+pub fn is_compressed(path: &Path, dir: &Path) -> bool {
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let (Ok(dir), Ok(parent)) = (fs::canonicalize(dir), fs::canonicalize(parent)) else {
+        return false;
+    };
+    let linked = fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink());
+    linked
+        && parent == dir
+        && is_capture_name(path)
+        && fs::canonicalize(path).is_ok_and(|real| is_compressed_object(&real) && real.parent() == Some(dir.join(OBJECTS_DIR).as_path()))
+}
+
+/// 打开保存文件，已压缩的内容读取时解压
+pub fn open(capture: &Capture) -> io::Result<Box<dyn Read>> {
+    let file = File::open(&capture.path)?;
+    if capture.compressed() {
+        Ok(Box::new(MultiGzDecoder::new(file)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// 文件名是否由包装器生成：`前缀_pid_日期_时间_随机数.txt`
+fn is_generated_name(path: &Path) -> bool {
+    let saved = saved_path(path);
+    let Some(stem) = saved.file_stem().and_then(|stem| stem.to_str()) else {
+        return false;
+    };
+    let parts: Vec<&str> = stem.rsplitn(5, '_').collect();
+    let digits = |part: &str, len: usize| part.len() == len && part.bytes().all(|b| b.is_ascii_digit());
+    match parts[..] {
+        [random, time, date, pid, prefix] => {
+            random.len() == 8
+                && random.bytes().all(|b| b.is_ascii_hexdigit())
+                && digits(time, 6)
+                && digits(date, 8)
+                && !pid.is_empty()
+                && pid.bytes().all(|b| b.is_ascii_digit())
+                && !prefix.is_empty()
+        }
+        _ => false,
+    }
+}
+
+/// 列出保存目录中的保存文件，按修改时间从早到晚排序；目录不存在时返回空列表
+///
+/// 只认有元数据或文件名由包装器生成的文件，保存目录中的其他 `.txt` 不会被清理或压缩
+pub fn scan(dir: &Path) -> io::Result<Vec<Capture>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    // 同一秒内可能保存多个文件，按完整精度的修改时间排序
+    let mut captures: Vec<(Duration, Capture)> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| is_capture_name(&entry.path()))
+        .filter_map(|entry| {
+            let path = entry.path();
+            // 链接本身的时间是这次保存的时间，内容的大小取链接指向的文件
+            let link = entry.metadata().ok()?;
+            let stat = fs::metadata(&path).ok().filter(|stat| stat.is_file())?;
+            let modified = link.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+            let object = link.file_type().is_symlink().then(|| fs::read_link(&path).ok().map(|target| dir.join(target))).flatten();
+            let meta: Option<CaptureMeta> = fs::read_to_string(meta_path(&path)).ok().and_then(|raw| serde_json::from_str(&raw).ok());
+            if meta.is_none() && !is_generated_name(&path) {
+                return None;
+            }
+            let recording = object.is_none() && is_locked(&path);
+            Some((modified, Capture { path, modified: modified.as_secs() as i64, bytes: stat.len(), object, meta, recording }))
+        })
+        .collect();
+    captures.sort_by(|(a, first), (b, second)| a.cmp(b).then_with(|| first.path.cmp(&second.path)));
+    Ok(captures.into_iter().map(|(_, capture)| capture).collect())
+}
+
+/// 与已有内容相同的保存文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identical {
+    /// 最近一次保存相同内容的文件
+    pub path: PathBuf,
+    /// 该文件的保存时间（Unix 秒）
+    pub modified: i64,
+}
+
+/// 把保存文件移入 `objects/<sha256>`，原位置换成指向它的链接
+///
+/// 内容已经存在时直接丢弃这份副本，返回最近一次保存相同内容的文件
+pub fn store(capture: &Path, sha256: &str) -> io::Result<Option<Identical>> {
+    let dir = capture.parent().unwrap_or(Path::new("."));
+    let objects = dir.join(OBJECTS_DIR);
+    fs::create_dir_all(&objects)?;
+    // 只链接未压缩的内容，刚保存的文件名必须能直接读取
+    let target = Path::new(OBJECTS_DIR).join(sha256);
+    let object = dir.join(&target);
+
+    let identical = if object.is_file() {
+        latest_link(dir, &target, capture)
+    } else {
+        fs::rename(capture, &object)?;
+        None
+    };
+    if let Err(e) = relink(capture, &target, None) {
+        if !capture.exists() {
+            let _ = fs::copy(&object, capture);
+        }
+        return Err(e);
+    }
+    Ok(identical)
+}
+
+/// 压缩后的内容相对保存目录的路径
+fn compressed_target(sha256: &str) -> PathBuf {
+    Path::new(OBJECTS_DIR).join(format!("{}.{}", sha256, COMPRESSED_EXTENSION))
+}
+
+/// 把 path 换成指向 target 的链接；modified 为链接本身的时间，None 表示当前时间
+///
+/// 先建临时链接再改名，原位置始终有完整的内容
+fn relink(path: &Path, target: &Path, modified: Option<SystemTime>) -> io::Result<()> {
+    let link = path.with_extension("link.tmp");
+    let _ = fs::remove_file(&link);
+    let linked = std::os::unix::fs::symlink(target, &link)
+        .and_then(|_| modified.map_or(Ok(()), |time| set_link_modified(&link, time)))
+        .and_then(|_| fs::rename(&link, path));
+    if linked.is_err() {
+        let _ = fs::remove_file(&link);
+    }
+    linked
+}
+
+/// 修改链接本身（而不是它指向的文件）的修改时间
+fn set_link_modified(path: &Path, modified: SystemTime) -> io::Result<()> {
+    let since = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let time = libc::timespec { tv_sec: since.as_secs() as libc::time_t, tv_nsec: since.subsec_nanos() as libc::c_long };
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    if unsafe { libc::utimensat(libc::AT_FDCWD, path.as_ptr(), [time, time].as_ptr(), libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// 相对时间：`42 秒前`、`2 分钟前`、`3 小时前`、`5 天前`
+pub fn ago(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    match seconds {
+        0..60 => format!("{} 秒前", seconds),
+        60..3600 => format!("{} 分钟前", seconds / 60),
+        3600..86400 => format!("{} 小时前", seconds / 3600),
+        _ => format!("{} 天前", seconds / 86400),
+    }
+}
+
+/// 指向 target 的链接中最新的一个（不含 except）
+fn latest_link(dir: &Path, target: &Path, except: &Path) -> Option<Identical> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path() != except && fs::read_link(entry.path()).is_ok_and(|link| link == target))
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+            Some(Identical { path: entry.path(), modified: modified.as_secs() as i64 })
+        })
+        .max_by_key(|identical| identical.modified)
+}
+
+/// 删除没有链接引用的内容
+fn prune_objects(dir: &Path) -> io::Result<()> {
+    let objects = match fs::read_dir(dir.join(OBJECTS_DIR)) {
+        Ok(objects) => objects,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let referenced: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .filter_map(|entry| fs::read_link(entry.path()).ok())
+        .map(|target| dir.join(target))
+        .collect();
+    for object in objects.filter_map(Result::ok) {
+        let recent = object
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age.as_secs() < OBJECT_GRACE_SECS));
+        if !recent && !referenced.contains(&object.path()) {
+            let _ = fs::remove_file(object.path());
+        }
+    }
+    Ok(())
+}
+
+/// 用 gzip 压缩保存文件的内容，返回压缩后的大小
+///
+/// 指向同一内容的保存文件一起改名为 `<原文件名>.gz` 并指向压缩后的内容，保留原来的时间；
+/// 原来的内容在下次清理时删除
+pub fn compress(capture: &Capture, dir: &Path) -> io::Result<u64> {
+    // 内容文件以 SHA-256 命名；没有链接的文件（跟随模式）保存之后可能还有追加，重新计算
+    let sha256 = match &capture.object {
+        Some(object) => object.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_string(),
+        None => {
+            let mut hasher = Sha256::new();
+            io::copy(&mut File::open(&capture.path)?, &mut hasher)?;
+            format!("{:x}", hasher.finalize())
+        }
+    };
+    let target = compressed_target(&sha256);
+    let object = dir.join(&target);
+    fs::create_dir_all(dir.join(OBJECTS_DIR))?;
+    if !object.is_file() {
+        let temp = object.with_extension("gz.tmp");
+        let written = File::create(&temp).and_then(|file| {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            io::copy(&mut File::open(capture.storage())?, &mut encoder)?;
+            encoder.finish()?.sync_all()?;
+            fs::rename(&temp, &object)
+        });
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+    }
+
+    let links: Vec<PathBuf> = match &capture.object {
+        Some(old) => fs::read_dir(dir)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| fs::read_link(path).is_ok_and(|link| dir.join(link) == *old))
+            .collect(),
+        None => vec![capture.path.clone()],
+    };
+    for link in links {
+        let modified = fs::symlink_metadata(&link).and_then(|meta| meta.modified()).ok();
+        let mut name = link.clone().into_os_string();
+        name.push(format!(".{}", COMPRESSED_EXTENSION));
+        relink(Path::new(&name), &target, modified)?;
+        fs::remove_file(&link)?;
+    }
+    Ok(fs::metadata(&object)?.len())
+}
+
+/// 删除保存文件及其元数据，并从索引中去掉对应的行，返回删除的文件数
+///
+/// 不再被任何保存文件引用的内容同时删除
+pub fn remove(captures: &[&Capture], dir: &Path) -> io::Result<usize> {
+    let mut removed = Vec::new();
+    for capture in captures {
+        match fs::remove_file(&capture.path) {
+            Ok(()) => removed.push(saved_path(&capture.path).display().to_string()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let _ = fs::remove_file(meta_path(&capture.path));
+    }
+    if removed.is_empty() {
+        return Ok(0);
+    }
+    prune_objects(dir)?;
+
+    let mut file = match OpenOptions::new().read(true).write(true).open(index_path(dir)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(removed.len()),
+        Err(e) => return Err(e),
+    };
+    lock_exclusive(&file)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    let kept: String = content
+        .lines()
+        .filter(|line| {
+            let path = serde_json::from_str::<CaptureMeta>(line).map(|meta| meta.path);
+            !path.is_ok_and(|path| removed.contains(&path))
+        })
+        .map(|line| format!("{}\n", line))
+        .collect();
+    file.seek(SeekFrom::Start(0))?;
+    file.set_len(0)?;
+    file.write_all(kept.as_bytes())?;
+    Ok(removed.len())
+}
+
+/// 限制写入字节数的保存文件，超过配额时写入失败（`ErrorKind::QuotaExceeded`）
+pub struct QuotaWriter<W> {
+    inner: W,
+    /// 0 表示不限
+    limit: u64,
+    written: u64,
+}
+
+impl<W: Write> QuotaWriter<W> {
+    pub fn new(inner: W, limit: u64) -> Self {
+        QuotaWriter { inner, limit, written: 0 }
+    }
+
+    /// 向已有 written 字节的文件追加，已有内容计入配额
+    pub fn appending(inner: W, limit: u64, written: u64) -> Self {
+        QuotaWriter { inner, limit, written }
+    }
+}
+
+impl<W: Write> Write for QuotaWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.limit > 0 && self.written + buf.len() as u64 > self.limit {
+            return Err(io::Error::new(io::ErrorKind::QuotaExceeded, format!("超过保存配额（{} 字节）", self.limit)));
+        }
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 打印因超过配额而没有保存的提示（统一格式）
+pub fn print_quota_message(limit: u64) {
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    eprintln!("⚠️  [Cursor 强制最佳实践] 管道输入超过保存配额（{} 字节），本次没有保存", limit);
+    eprintln!("💡 命令已正常执行；需要保存时调大 [capture] max_total_bytes，或先把输出写入文件再查看");
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+}
+
+/// 一次清理的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// 删除的文件数
+    pub removed: usize,
+    /// 释放的字节数
+    pub freed: u64,
+    /// 剩余的文件数
+    pub kept: usize,
+    /// 剩余的字节数
+    pub kept_bytes: u64,
+    /// 压缩的内容数
+    pub compressed: usize,
+    /// 压缩节省的字节数
+    pub saved: u64,
+}
+
+/// 按上限挑出要删除的保存文件（captures 按时间从早到晚排序），最早的先删除
+///
+/// protect 是刚保存的文件，不会被删除；跟随模式仍在追加的文件也不删除
+pub fn select_expired<'a>(captures: &'a [Capture], config: &CaptureConfig, now: i64, protect: Option<&Path>) -> Vec<&'a Capture> {
+    let protected = |capture: &Capture| capture.recording || protect.is_some_and(|path| capture.path == path);
+    let mut evict: Vec<bool> = captures
+        .iter()
+        .map(|capture| config.max_age_secs > 0 && now - capture.modified > config.max_age_secs as i64 && !protected(capture))
+        .collect();
+
+    if config.max_files_per_session > 0 {
+        let mut sessions: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (i, capture) in captures.iter().enumerate().filter(|(i, _)| !evict[*i]) {
+            if let Some(session) = capture.session() {
+                sessions.entry(session).or_default().push(i);
+            }
+        }
+        for indices in sessions.values() {
+            let excess = indices.len().saturating_sub(config.max_files_per_session as usize);
+            for &i in indices.iter().filter(|&&i| !protected(&captures[i])).take(excess) {
+                evict[i] = true;
+            }
+        }
+    }
+
+    if config.max_total_bytes > 0 {
+        // 指向同一内容的链接只算一份，最后一个链接删除时才释放空间
+        let mut links: BTreeMap<&Path, usize> = BTreeMap::new();
+        for capture in captures.iter().zip(&evict).filter(|(_, evicted)| !**evicted).map(|(c, _)| c) {
+            *links.entry(capture.storage()).or_default() += 1;
+        }
+        let mut total: u64 = captures
+            .iter()
+            .filter(|capture| links.contains_key(capture.storage()))
+            .map(|capture| (capture.storage(), capture.bytes))
+            .collect::<BTreeMap<_, _>>()
+            .values()
+            .sum();
+        for (i, capture) in captures.iter().enumerate() {
+            if total <= config.max_total_bytes {
+                break;
+            }
+            if !evict[i] && !protected(capture) {
+                evict[i] = true;
+                let remaining = links.get_mut(capture.storage()).map(|count| {
+                    *count -= 1;
+                    *count
+                });
+                if remaining == Some(0) {
+                    total -= capture.bytes;
+                }
+            }
+        }
+    }
+
+    captures.iter().zip(evict).filter(|(_, evicted)| *evicted).map(|(capture, _)| capture).collect()
+}
+
+/// 挑出要压缩的内容：保存超过 compress_after_secs 或大于 compress_above_bytes，共用的内容只取最新的链接
+///
+/// protect 是刚保存的文件，不会被压缩；跟随模式仍在追加的文件不压缩，
+/// 没有链接的文件最后修改不到 settle_secs 秒的也不压缩
+pub fn select_compressible<'a>(
+    captures: &'a [Capture],
+    config: &CaptureConfig,
+    now: i64,
+    protect: Option<&Path>,
+    settle_secs: u64,
+) -> Vec<&'a Capture> {
+    let protected = protect.and_then(|path| captures.iter().find(|capture| capture.path == path)).map(Capture::storage);
+    // captures 按时间从早到晚排序，后面的链接更新
+    let mut latest: BTreeMap<&Path, &Capture> = BTreeMap::new();
+    for capture in captures {
+        latest.insert(capture.storage(), capture);
+    }
+    let mut selected: Vec<&Capture> = latest
+        .into_values()
+        .filter(|capture| {
+            let age = now - capture.modified;
+            let old = config.compress_after_secs > 0 && age > config.compress_after_secs as i64;
+            let large = config.compress_above_bytes > 0 && capture.bytes > config.compress_above_bytes;
+            let settled = capture.object.is_some() || age >= settle_secs as i64;
+            (old || large) && settled && !capture.recording && !capture.compressed() && protected != Some(capture.storage())
+        })
+        .collect();
+    selected.sort_by_key(|capture| capture.modified);
+    selected
+}
+
+/// 实际占用的磁盘空间（共用的内容只算一次）
+pub fn stored_bytes<'a>(captures: impl Iterator<Item = &'a Capture>) -> u64 {
+    captures.map(|capture| (capture.storage(), capture.bytes)).collect::<BTreeMap<_, _>>().values().sum()
+}
+
+/// 清理保存目录；dry_run 时只统计不删除
+pub fn gc(dir: &Path, config: &CaptureConfig, now: i64, protect: Option<&Path>, dry_run: bool) -> io::Result<(GcReport, Vec<Capture>)> {
+    let captures = scan(dir)?;
+    let expired = select_expired(&captures, config, now, protect);
+    let removed = if dry_run { expired.len() } else { remove(&expired, dir)? };
+    let kept: Vec<Capture> = captures.iter().filter(|capture| !expired.contains(capture)).cloned().collect();
+    let total = stored_bytes(captures.iter());
+    let freed = total - stored_bytes(kept.iter());
+
+    let compressible = select_compressible(&kept, config, now, protect, config::global().follow.record_secs);
+    let mut saved = 0;
+    if !dry_run && !compressible.is_empty() {
+        for capture in &compressible {
+            saved += capture.bytes.saturating_sub(compress(capture, dir)?);
+        }
+        prune_objects(dir)?;
+    }
+    let report = GcReport {
+        removed,
+        freed,
+        kept: kept.len(),
+        kept_bytes: total - freed - saved,
+        compressed: compressible.len(),
+        saved,
+    };
+    let expired = expired.into_iter().cloned().collect();
+    Ok((report, expired))
+}
+
+/// 解压到临时目录的保存文件，drop 时删除临时目录
+#[derive(Debug, Default)]
+pub struct Extracted {
+    dir: Option<PathBuf>,
+    /// (参数中的保存文件, 解压后的文件)
+    pub files: Vec<(String, PathBuf)>,
+}
+
+impl Extracted {
+    /// 存放解压文件的临时目录
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+}
+
+impl Drop for Extracted {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// 把参数中指向保存目录 dir 中已压缩保存文件的路径换成解压后的普通文件
+///
+/// 按保存时的 `.txt` 文件名给出、已经压缩为 `.txt.gz` 的也会解压。
+/// 解压后的文件使用保存时的文件名，放在只有当前用户能读的临时目录中；同一个文件出现多次只解压一次。
+/// 解压失败时参数保持不变
+pub fn extract_args(args: &mut [String], dir: &Path) -> io::Result<Extracted> {
+    let mut extracted = Extracted::default();
+    let mut replaced = args.to_vec();
+    for arg in replaced.iter_mut().filter(|arg| !arg.starts_with('-')) {
+        let renamed = PathBuf::from(format!("{}.{}", arg, COMPRESSED_EXTENSION));
+        let source = if is_compressed(Path::new(arg.as_str()), dir) {
+            PathBuf::from(arg.as_str())
+        } else if fs::symlink_metadata(arg.as_str()).is_err() && is_compressed(&renamed, dir) {
+            renamed
+        } else {
+            continue;
+        };
+        if let Some((_, plain)) = extracted.files.iter().find(|(path, _)| path == arg) {
+            *arg = plain.display().to_string();
+            continue;
+        }
+        let temp = match &extracted.dir {
+            Some(temp) => temp.clone(),
+            None => {
+                let temp = env::temp_dir().join(format!("cursor-guard-{}", process::id()));
+                DirBuilder::new().recursive(true).mode(0o700).create(&temp)?;
+                extracted.dir = Some(temp.clone());
+                temp
+            }
+        };
+        let name = saved_path(&source).file_name().map(|name| name.to_os_string()).unwrap_or_default();
+        let mut plain = temp.join(&name);
+        if extracted.files.iter().any(|(_, used)| *used == plain) {
+            plain = temp.join(format!("{}_{}", extracted.files.len(), name.to_string_lossy()));
+        }
+        io::copy(&mut MultiGzDecoder::new(File::open(&source)?), &mut File::create(&plain)?)?;
+        extracted.files.push((arg.clone(), plain.clone()));
+        *arg = plain.display().to_string();
+    }
+    args.clone_from_slice(&replaced);
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procfs::fake::FakeProc;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_shell_script() {
+        assert_eq!(shell_script(&args(&["bash", "-c", "cargo build | tail"])).as_deref(), Some("cargo build | tail"));
+        assert_eq!(shell_script(&args(&["/bin/bash", "-lc", "make"])).as_deref(), Some("make"));
+        assert_eq!(shell_script(&args(&["bash", "-c", "-l", "make"])).as_deref(), Some("make"));
+        assert_eq!(shell_script(&args(&["bash", "--login"])), None);
+        assert_eq!(shell_script(&args(&["bash", "script.sh"])), None);
+    }
+
+    #[test]
+    fn test_origin_from_shell_and_pipe_writer() {
+        // node(10) -> bash -c '...'(20) -> cargo(30) | tail(31)
+        let fake = FakeProc::new("capture_origin");
+        fake.process(1, 0, "init")
+            .process(10, 1, "node")
+            .process(20, 10, "bash")
+            .cmdline(20, &["bash", "-c", "cargo build 2>&1 | tail -n 5"])
+            .process(30, 20, "cargo")
+            .cmdline(30, &["cargo", "build"])
+            .pipe_fd(30, 1, 600, true)
+            .process(31, 20, "tail")
+            .pipe_fd(31, 0, 600, false);
+        let proc = ProcFs::new(&fake.root);
+        let origin = origin_of(&proc, 31, &pipeline::scan(&proc, 31, Some(600), None));
+        assert_eq!(origin.pipeline.as_deref(), Some("cargo build 2>&1 | tail -n 5"));
+        assert_eq!(origin.upstream, vec!["cargo build"]);
+
+        // 标准输入不是管道、shell 没有 -c 时没有来源
+        fake.cmdline(20, &["bash"]);
+        assert_eq!(origin_of(&proc, 31, &pipeline::scan(&proc, 31, None, None)), Origin::default());
+    }
+
+    #[test]
+    fn test_describe_and_write() {
+        let dir = std::env::temp_dir().join(format!("cursor_test_capture_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let capture = dir.join("tail_1_20260101_000000_00000000.txt");
+        fs::write(&capture, "one\ntwo\n").unwrap();
+
+        let origin = Origin { pipeline: Some("seq 2 | tail".to_string()), upstream: vec!["seq 2".to_string()] };
+        let meta = describe(&capture, &origin, "tail -n 100").unwrap();
+        assert_eq!((meta.bytes, meta.lines), (8, 2));
+        assert_eq!(meta.sha256, format!("{:x}", Sha256::digest(b"one\ntwo\n")));
+
+        let index = index_path(&dir);
+        write(&meta, &index).unwrap();
+        write(&meta, &index).unwrap();
+        let sidecar: CaptureMeta = serde_json::from_str(&fs::read_to_string(meta_path(&capture)).unwrap()).unwrap();
+        assert_eq!(sidecar, meta);
+        assert!(meta_path(&capture).ends_with("tail_1_20260101_000000_00000000.meta.json"));
+        let entries: Vec<CaptureMeta> =
+            fs::read_to_string(&index).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(entries, vec![meta.clone(), meta]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_and_remove() {
+        let dir = std::env::temp_dir().join(format!("cursor_test_capture_scan_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let old = dir.join("tail_input_cursor_1_20260101_000000_00000001.txt");
+        let new = dir.join("grep_input_2_20260101_000001_00000002.txt");
+        fs::write(&old, "a\n").unwrap();
+        fs::write(&new, "b\n").unwrap();
+        File::options().write(true).open(&old).unwrap().set_modified(UNIX_EPOCH + Duration::from_secs(100)).unwrap();
+        let index = index_path(&dir);
+        let meta = describe(&old, &Origin::default(), "tail -n 100").unwrap();
+        write(&meta, &index).unwrap();
+        // 索引中其他文件的记录保留
+        let other = CaptureMeta { path: "/elsewhere.txt".to_string(), ..meta };
+        let mut file = OpenOptions::new().append(true).open(&index).unwrap();
+        writeln!(file, "{}", serde_json::to_string(&other).unwrap()).unwrap();
+
+        let captures = scan(&dir).unwrap();
+        assert_eq!(captures.len(), 2);
+        assert_eq!((captures[0].path.clone(), captures[0].modified), (old.clone(), 100));
+        assert_eq!(captures[0].command(), "tail -n 100");
+        assert_eq!(captures[1].command(), "grep_input");
+        assert_eq!(captures[1].session(), None);
+
+        assert_eq!(remove(&[&captures[0]], &dir).unwrap(), 1);
+        assert!(!old.exists() && !meta_path(&old).exists());
+        let remaining = fs::read_to_string(&index).unwrap();
+        assert_eq!(remaining.lines().count(), 1);
+        assert!(remaining.contains("/elsewhere.txt"));
+        assert_eq!(scan(&dir).unwrap().len(), 1);
+        assert!(scan(&dir.join("missing")).unwrap().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn stored(name: &str, modified: i64, bytes: u64, session: Option<&str>) -> Capture {
+        let meta = session.map(|session| CaptureMeta {
+            path: name.to_string(),
+            created: String::new(),
+            command: "tail".to_string(),
+            pipeline: None,
+            upstream: Vec::new(),
+            cwd: "/".to_string(),
+            agent: None,
+            session: session.to_string(),
+            bytes,
+            lines: 1,
+            sha256: String::new(),
+        });
+        Capture { path: PathBuf::from(name), modified, bytes, object: None, meta, recording: false }
+    }
+
+    fn names(captures: Vec<&Capture>) -> Vec<String> {
+        captures.iter().map(|c| c.path.display().to_string()).collect()
+    }
+
+    #[test]
+    fn test_select_expired_by_age_session_and_size() {
+        let limits = |age, total, files| CaptureConfig {
+            max_age_secs: age,
+            max_total_bytes: total,
+            max_files_per_session: files,
+            ..CaptureConfig::default()
+        };
+        let captures = vec![
+            stored("a", 100, 10, Some("s1")),
+            stored("b", 200, 10, Some("s2")),
+            stored("c", 300, 10, Some("s1")),
+            stored("d", 400, 10, Some("s1")),
+            stored("e", 500, 10, None),
+        ];
+        assert!(select_expired(&captures, &limits(0, 0, 0), 1000, None).is_empty());
+        assert_eq!(names(select_expired(&captures, &limits(750, 0, 0), 1000, None)), vec!["a", "b"]);
+        // 每个会话只删除最早的
+        assert_eq!(names(select_expired(&captures, &limits(0, 0, 2), 1000, None)), vec!["a"]);
+        assert_eq!(names(select_expired(&captures, &limits(0, 25, 0), 1000, None)), vec!["a", "b", "c"]);
+        // 刚保存的文件不删除
+        assert_eq!(names(select_expired(&captures, &limits(0, 25, 0), 1000, Some(Path::new("a")))), vec!["b", "c", "d"]);
+        assert_eq!(names(select_expired(&captures, &limits(750, 25, 1), 1000, None)), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_quota_writer() {
+        let mut writer = QuotaWriter::new(Vec::new(), 5);
+        writer.write_all(b"abc").unwrap();
+        let error = writer.write_all(b"def").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::QuotaExceeded);
+        assert_eq!(writer.inner, b"abc");
+        let mut unlimited = QuotaWriter::new(Vec::new(), 0);
+        unlimited.write_all(&[0; 100]).unwrap();
+    }
+
+    #[test]
+    fn test_store_deduplicates_identical_input() {
+        let dir = std::env::temp_dir().join(format!("cursor_test_capture_store_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("grep_input_1_20260101_000000_00000001.txt");
+        let second = dir.join("grep_input_2_20260101_000000_00000002.txt");
+        let sha = format!("{:x}", Sha256::digest(b"big log\n"));
+        fs::write(&first, "big log\n").unwrap();
+        assert_eq!(store(&first, &sha).unwrap(), None);
+        fs::write(&second, "big log\n").unwrap();
+        let identical = store(&second, &sha).unwrap().unwrap();
+        assert_eq!(identical.path, first);
+
+        let object = dir.join(OBJECTS_DIR).join(&sha);
+        assert_eq!(fs::read_to_string(&second).unwrap(), "big log\n");
+        assert_eq!(fs::read_dir(dir.join(OBJECTS_DIR)).unwrap().count(), 1);
+        let captures = scan(&dir).unwrap();
+        assert_eq!(captures.len(), 2);
+        assert!(captures.iter().all(|capture| capture.object.as_deref() == Some(object.as_path())));
+        assert_eq!(stored_bytes(captures.iter()), 8);
+
+        // 内容在最后一个链接删除后才删除（跳过新内容的保护期）
+        File::options().write(true).open(&object).unwrap().set_modified(UNIX_EPOCH).unwrap();
+        remove(&[&captures[0]], &dir).unwrap();
+        assert!(object.exists());
+        remove(&[&captures[1]], &dir).unwrap();
+        assert!(!object.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_shared_content_is_counted_once() {
+        let shared = |name: &str, modified| Capture { object: Some(PathBuf::from("objects/x")), ..stored(name, modified, 10, None) };
+        let captures = vec![shared("a", 100), shared("b", 200), stored("c", 300, 10, None)];
+        let config = CaptureConfig { max_age_secs: 0, max_total_bytes: 15, max_files_per_session: 0, ..CaptureConfig::default() };
+        // 删除 a 不释放空间，b 删除后共用的内容才释放
+        assert_eq!(names(select_expired(&captures, &config, 1000, None)), vec!["a", "b"]);
+        assert_eq!(ago(30), "30 秒前");
+        assert_eq!(ago(150), "2 分钟前");
+        assert_eq!(ago(2 * 86400), "2 天前");
+    }
+
+    #[test]
+    fn test_gc_keeps_foreign_files() {
+        let dir = std::env::temp_dir().join(format!("cursor_test_capture_foreign_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let notes = dir.join("notes.txt");
+        let readme = dir.join("README_2_20260101_000000.txt");
+        let capture = dir.join("tail_input_7_20260101_000000_0000abcd.txt");
+        for path in [&notes, &readme, &capture] {
+            fs::write(path, "x".repeat(100)).unwrap();
+            File::options().write(true).open(path).unwrap().set_modified(UNIX_EPOCH + Duration::from_secs(100)).unwrap();
+        }
+        let config = CaptureConfig {
+            max_age_secs: 10,
+            max_total_bytes: 1,
+            max_files_per_session: 1,
+            compress_after_secs: 10,
+            compress_above_bytes: 1,
+            ..CaptureConfig::default()
+        };
+        let (report, _) = gc(&dir, &config, 100_000, None, false).unwrap();
+        assert_eq!((report.removed, report.kept), (1, 0));
+        assert!(!capture.exists());
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "x".repeat(100));
+        assert_eq!(fs::read_to_string(&readme).unwrap(), "x".repeat(100));
+        assert!(is_generated_name(Path::new("grep_input_cursor_12_20261018_055900_3fb96a7b.txt.gz")));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_gc_skips_recording_follow_files() {
+        let dir = std::env::temp_dir().join(format!("cursor_test_capture_recording_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let follow = dir.join("tail_follow_7_20260101_000000_0000abcd.txt");
+        fs::write(&follow, "x".repeat(100)).unwrap();
+        File::options().write(true).open(&follow).unwrap().set_modified(UNIX_EPOCH + Duration::from_secs(100)).unwrap();
+        let config = CaptureConfig { max_age_secs: 10, max_total_bytes: 1, compress_after_secs: 10, ..CaptureConfig::default() };
+
+        // 后台记录进程持有锁时既不删除也不压缩
+        let lock = File::open(&follow).unwrap();
+        lock_exclusive(&lock).unwrap();
+        assert!(scan(&dir).unwrap()[0].recording);
+        let (report, _) = gc(&dir, &config, 100_000, None, false).unwrap();
+        assert_eq!((report.removed, report.compressed), (0, 0));
+        assert_eq!(fs::read_to_string(&follow).unwrap(), "x".repeat(100));
+
+        drop(lock);
+        assert!(!scan(&dir).unwrap()[0].recording);
+        let (report, _) = gc(&dir, &config, 100_000, None, false).unwrap();
+        assert_eq!(report.removed, 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_select_compressible_by_age_and_size() {
+        let limits = |after, above| CaptureConfig { compress_after_secs: after, compress_above_bytes: above, ..CaptureConfig::default() };
+        let linked = |name: &str, object: &str, modified, bytes| Capture { object: Some(PathBuf::from(object)), ..stored(name, modified, bytes, None) };
+        let captures = vec![
+            linked("a", "objects/x", 100, 10),
+            linked("b", "objects/y.gz", 200, 5),
+            stored("c", 300, 50, None),
+            linked("d", "objects/x", 900, 10),
+            linked("e", "objects/z", 950, 100),
+        ];
+        assert!(select_compressible(&captures, &limits(0, 0), 1000, None, 0).is_empty());
+        // 共用的内容按最新的链接判断，已压缩的跳过
+        assert_eq!(names(select_compressible(&captures, &limits(500, 0), 1000, None, 0)), vec!["c"]);
+        assert_eq!(names(select_compressible(&captures, &limits(50, 0), 1000, None, 0)), vec!["c", "d"]);
+        assert_eq!(names(select_compressible(&captures, &limits(0, 20), 1000, None, 0)), vec!["c", "e"]);
+        // 刚保存的文件和可能还在追加的跟随文件不压缩
+        assert_eq!(names(select_compressible(&captures, &limits(50, 20), 1000, Some(Path::new("a")), 0)), vec!["c", "e"]);
+        assert_eq!(names(select_compressible(&captures, &limits(0, 20), 1000, None, 800)), vec!["e"]);
+    }
+
+    #[test]
+    fn test_compress_and_extract() {
+        let dir = std::env::temp_dir().join(format!("cursor_test_capture_compress_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let content = "error: line\n".repeat(1000);
+        let sha = format!("{:x}", Sha256::digest(content.as_bytes()));
+        let first = dir.join("grep_input_1_20260101_000000_00000001.txt");
+        let second = dir.join("grep_input_2_20260101_000000_00000002.txt");
+        let follow = dir.join("tail_follow_3_20260101_000000_00000003.txt");
+        for path in [&first, &second] {
+            fs::write(path, &content).unwrap();
+            store(path, &sha).unwrap();
+        }
+        fs::write(&follow, "tail\n").unwrap();
+        set_link_modified(&first, UNIX_EPOCH + Duration::from_secs(100)).unwrap();
+
+        let captures = scan(&dir).unwrap();
+        assert_eq!(compress(&captures[0], &dir).unwrap(), fs::metadata(dir.join(compressed_target(&sha))).unwrap().len());
+        compress(&captures[2], &dir).unwrap();
+        let captures = scan(&dir).unwrap();
+        assert!(captures.iter().all(Capture::compressed));
+        // 压缩后改名为 .txt.gz，保留原来的时间和元数据位置，共用的内容一起压缩
+        let gz = |path: &Path| PathBuf::from(format!("{}.gz", path.display()));
+        assert!(!first.exists() && !second.exists() && !follow.exists());
+        assert_eq!((captures[0].path.clone(), captures[0].modified), (gz(&first), 100));
+        assert_eq!(saved_path(&captures[0].path), first);
+        assert_eq!(meta_path(&captures[0].path), meta_path(&first));
+        assert_eq!(captures[0].object, captures[1].object);
+        assert!(captures[0].bytes < content.len() as u64);
+        let mut read = String::new();
+        open(&captures[1]).unwrap().read_to_string(&mut read).unwrap();
+        assert_eq!(read, content);
+
+        // 不经过包装器：.gz 文件可以直接用 gzip 解压，留下的 .txt 都是纯文本
+        let mut unzipped = String::new();
+        MultiGzDecoder::new(File::open(gz(&second)).unwrap()).read_to_string(&mut unzipped).unwrap();
+        assert_eq!(unzipped, content);
+        let third = dir.join("grep_input_4_20260101_000000_00000004.txt");
+        fs::write(&third, &content).unwrap();
+        assert_eq!(store(&third, &sha).unwrap(), None);
+        for entry in fs::read_dir(&dir).unwrap().filter_map(Result::ok) {
+            if entry.path().extension().is_some_and(|ext| ext == CAPTURE_EXTENSION) {
+                assert!(fs::read_to_string(entry.path()).is_ok(), "{} 不是纯文本", entry.path().display());
+            }
+        }
+
+        // 按压缩后的文件名或保存时的文件名给出都会解压
+        let mut args = vec![
+            "-n".to_string(),
+            "error".to_string(),
+            gz(&first).display().to_string(),
+            follow.display().to_string(),
+            first.display().to_string(),
+            third.display().to_string(),
+        ];
+        let extracted = extract_args(&mut args, &dir).unwrap();
+        assert_eq!(extracted.files.len(), 3);
+        assert_eq!(&args[..2], ["-n", "error"]);
+        assert!(args[2].ends_with("grep_input_1_20260101_000000_00000001.txt") && args[2] != first.display().to_string());
+        assert_eq!(fs::read_to_string(&args[2]).unwrap(), content);
+        assert_eq!(fs::read_to_string(&args[3]).unwrap(), "tail\n");
+        assert_ne!(args[2], args[4]);
+        assert_eq!(fs::read_to_string(&args[4]).unwrap(), content);
+        assert_eq!(args[5], third.display().to_string());
+        let temp = extracted.dir().unwrap().to_path_buf();
+        drop(extracted);
+        assert!(!temp.exists());
+
+        // 保存目录以外的 objects/*.gz 原样交给原始命令
+        let mut plain = vec!["error".to_string(), gz(&first).display().to_string()];
+        assert!(extract_args(&mut plain, &dir.join(OBJECTS_DIR)).unwrap().files.is_empty());
+        let vendor = dir.join(OBJECTS_DIR).join("vendor.gz");
+        fs::write(&vendor, "raw").unwrap();
+        let mut vendored = vec![vendor.display().to_string()];
+        assert!(extract_args(&mut vendored, &dir).unwrap().files.is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
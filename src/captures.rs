@@ -0,0 +1,698 @@
+//! `cursor-guard captures`：管理保存目录中的文件
+//!
+//! - `list`：按会话、时间、命令、大小筛选
+//! - `show`：查看保存文件，可以只看部分行
+//! - `grep`：用原始 grep 搜索所有保存文件
+//! - `path`：把 `latest`、`-2` 等引用解析为文件路径
+//! - `rm`：删除保存文件及其元数据
+//! - `gc`：按 `[capture]` 的上限清理并压缩较早或较大的内容（每次保存之后也会自动执行）
+//!
+//! 引用：`latest`（或 `-1`）是最新的保存文件，`-2` 是倒数第二个，也可以直接给文件名或路径。
+//! `show` 和 `grep` 直接读取已压缩的保存文件。
+
+use crate::stats::{display_width, pad};
+
+use cursor_common as common;
+use common::capture::{self, Capture, CaptureMeta};
+use common::{config, guard, session};
+use common::resolve::resolve_original;
+
+use chrono::{Local, TimeZone};
+use serde::Serialize;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const RULE: &str = "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━";
+
+const USAGE: &str = "\
+用法: cursor-guard captures <子命令> [参数...]
+  list [筛选条件] [--json]            列出保存文件（最新的在前）
+  show [引用] [--lines 起始:结束] [--number]
+                                     查看保存文件，行号从 1 开始，负数从末尾倒数
+  grep [筛选条件] <grep 参数...>     在保存文件中搜索
+  path [引用]                        输出保存文件的路径
+  rm <引用...> | <筛选条件> | --all  删除保存文件
+  gc [--dry-run]                     按保留时间、总大小和会话文件数上限清理，最早的先删除；
+                                     顺带压缩较早或较大的内容
+筛选条件: --session <会话|current> --newer <时长> --older <时长>
+          --command <文本> --min-size <大小> --max-size <大小>
+引用: latest、-1、-2……，或文件名、路径；时长: 30s、10m、2h、7d";
+
+/// 筛选条件
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Filter {
+    session: Option<String>,
+    /// 只保留最近多少秒内修改的
+    newer: Option<i64>,
+    /// 只保留多少秒以前修改的
+    older: Option<i64>,
+    /// 命令（包括管道命令和上游命令）中包含的文本
+    command: Option<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl Filter {
+    fn is_empty(&self) -> bool {
+        *self == Filter::default()
+    }
+
+    fn matches(&self, capture: &Capture, now: i64) -> bool {
+        let age = now - capture.modified;
+        let command = self.command.as_deref().is_none_or(|text| {
+            capture.command().contains(text)
+                || capture.meta.as_ref().is_some_and(|meta| {
+                    meta.command.contains(text) || meta.upstream.iter().any(|upstream| upstream.contains(text))
+                })
+        });
+        self.session.as_deref().is_none_or(|session| capture.session() == Some(session))
+            && self.newer.is_none_or(|secs| age <= secs)
+            && self.older.is_none_or(|secs| age >= secs)
+            && self.min_size.is_none_or(|size| capture.bytes >= size)
+            && self.max_size.is_none_or(|size| capture.bytes <= size)
+            && command
+    }
+
+    /// 识别一个筛选选项，不是筛选选项时返回 Ok(false)
+    fn parse(&mut self, flag: &str, value: &mut dyn FnMut() -> Result<String, String>) -> Result<bool, String> {
+        match flag {
+            "--session" => {
+                let raw = value()?;
+                self.session = Some(if raw == "current" { session::id() } else { raw });
+            }
+            "--newer" => self.newer = Some(parse_duration_arg(flag, &value()?)?),
+            "--older" => self.older = Some(parse_duration_arg(flag, &value()?)?),
+            "--command" => self.command = Some(value()?),
+            "--min-size" => self.min_size = Some(parse_size_arg(flag, &value()?)?),
+            "--max-size" => self.max_size = Some(parse_size_arg(flag, &value()?)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// list/rm 的参数
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Selection {
+    filter: Filter,
+    refs: Vec<String>,
+    json: bool,
+    all: bool,
+}
+
+/// list 的 JSON 输出
+#[derive(Debug, Serialize)]
+struct Listed<'a> {
+    #[serde(rename = "ref")]
+    reference: String,
+    path: String,
+    modified: String,
+    bytes: u64,
+    compressed: bool,
+    meta: Option<&'a CaptureMeta>,
+}
+
+/// captures 入口：args[0] 是子命令
+pub fn run(args: Vec<String>) -> i32 {
+    let Some((subcommand, rest)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let dir = PathBuf::from(&config::global().capture.dir);
+    let captures = match capture::scan(&dir) {
+        Ok(captures) => captures,
+        Err(e) => {
+            eprintln!("❌ captures: 无法读取保存目录 {}: {}", dir.display(), e);
+            return 1;
+        }
+    };
+    let now = Local::now().timestamp();
+    let result = match subcommand.as_str() {
+        "list" => list(&captures, rest, &dir, now),
+        "show" => show(&captures, rest),
+        "grep" => grep(&captures, rest, &dir, now),
+        "path" => path(&captures, rest),
+        "rm" => remove(&captures, rest, &dir, now),
+        "gc" => collect(rest, &dir, now),
+        other => Err(format!("未知的子命令 '{}'", other)),
+    };
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("❌ captures: {}", e);
+            eprintln!("{}", USAGE);
+            2
+        }
+    }
+}
+
+fn list(captures: &[Capture], args: &[String], dir: &Path, now: i64) -> Result<i32, String> {
+    let selection = parse_selection(args)?;
+    if !selection.refs.is_empty() || selection.all {
+        return Err("list 只接受筛选条件".to_string());
+    }
+    // 最新的在前，引用按全部保存文件编号，与 path/show 一致
+    let selected: Vec<(String, &Capture)> = captures
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, capture)| (format!("-{}", captures.len() - i), capture))
+        .filter(|(_, capture)| selection.filter.matches(capture, now))
+        .collect();
+
+    if selection.json {
+        let listed: Vec<Listed> = selected
+            .iter()
+            .map(|(reference, capture)| Listed {
+                reference: reference.clone(),
+                path: capture.path.display().to_string(),
+                modified: format_time(capture.modified),
+                bytes: capture.bytes,
+                compressed: capture.compressed(),
+                meta: capture.meta.as_ref(),
+            })
+            .collect();
+        let json = serde_json::to_string_pretty(&listed).map_err(|e| e.to_string())?;
+        return Ok(emit(&format!("{}\n", json)));
+    }
+
+    let total = capture::stored_bytes(selected.iter().map(|(_, capture)| *capture));
+    let mut out = format!("📂 {}：{} 个保存文件（共 {}）\n", dir.display(), selected.len(), human_size(total));
+    if selected.is_empty() {
+        return Ok(emit(&out));
+    }
+    let headers = ["引用", "时间", "大小", "行数", "会话", "命令"];
+    let rows: Vec<[String; 6]> = selected
+        .iter()
+        .map(|(reference, capture)| {
+            [
+                reference.clone(),
+                format_time(capture.modified),
+                stored_size(capture),
+                capture.meta.as_ref().map_or("-".to_string(), |meta| meta.lines.to_string()),
+                capture.session().unwrap_or("-").to_string(),
+                capture.command(),
+            ]
+        })
+        .collect();
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|i| rows.iter().map(|row| display_width(&row[i])).chain([display_width(headers[i])]).max().unwrap_or(0))
+        .collect();
+    let render = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(cell, width)| pad(cell, *width)).collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    out.push_str(&format!("{}\n", render(headers.to_vec())));
+    for row in &rows {
+        out.push_str(&format!("{}\n", render(row.iter().map(String::as_str).collect())));
+    }
+    Ok(emit(&out))
+}
+
+/// 写到标准输出；下游已关闭（例如 `| head`）不算错误
+fn emit(text: &str) -> i32 {
+    let mut out = io::stdout().lock();
+    match out.write_all(text.as_bytes()).and_then(|_| out.flush()) {
+        Ok(()) => 0,
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => 0,
+        Err(e) => {
+            eprintln!("❌ captures: {}", e);
+            1
+        }
+    }
+}
+
+fn show(captures: &[Capture], args: &[String]) -> Result<i32, String> {
+    let mut reference = None;
+    let mut range = None;
+    let mut number = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.split_once('=') {
+            Some(("--lines", value)) => range = Some(parse_range(value)?),
+            _ if arg == "--lines" => range = Some(parse_range(iter.next().ok_or("--lines 需要参数")?)?),
+            _ if arg == "--number" || arg == "-n" => number = true,
+            _ if reference.is_none() => reference = Some(arg.clone()),
+            _ => return Err(format!("多余的参数 '{}'", arg)),
+        }
+    }
+    let Some(capture) = find(captures, reference.as_deref().unwrap_or("latest")) else {
+        return Ok(not_found(reference.as_deref().unwrap_or("latest")));
+    };
+
+    eprintln!("{}", RULE);
+    eprintln!("📄 {}", capture.path.display());
+    eprintln!("🧾 命令: {}", capture.command());
+    if let Some(meta) = &capture.meta {
+        eprintln!("📏 {} 行，{}，保存于 {}（{}）", meta.lines, human_size(meta.bytes), meta.created, meta.cwd);
+    }
+    if capture.compressed() {
+        eprintln!("📦 已压缩保存（{}），这里显示的是解压后的内容", human_size(capture.bytes));
+    }
+    eprintln!("{}", RULE);
+
+    match print_lines(capture, range, number) {
+        Ok(()) => Ok(0),
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(0),
+        Err(e) => {
+            eprintln!("❌ captures: 无法读取 {}: {}", capture.path.display(), e);
+            Ok(1)
+        }
+    }
+}
+
+/// 输出指定范围的行，range 为 None 时输出全部
+fn print_lines(capture: &Capture, range: Option<(Option<i64>, Option<i64>)>, number: bool) -> io::Result<()> {
+    let needs_total = range.is_some_and(|(start, end)| start.is_some_and(|n| n < 0) || end.is_some_and(|n| n < 0));
+    let total = if needs_total { count_lines(capture)? } else { u64::MAX };
+    let (first, last) = resolve_range(range.unwrap_or((None, None)), total);
+
+    let mut reader = BufReader::new(capture::open(capture)?);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut line = Vec::new();
+    let mut current = 0u64;
+    while current < last {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        current += 1;
+        if current < first {
+            continue;
+        }
+        if number {
+            write!(out, "{:>6}\t", current)?;
+        }
+        out.write_all(&line)?;
+    }
+    out.flush()
+}
+
+fn count_lines(capture: &Capture) -> io::Result<u64> {
+    let mut reader = BufReader::new(capture::open(capture)?);
+    let mut line = Vec::new();
+    let mut count = 0;
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        count += 1;
+        line.clear();
+    }
+    Ok(count)
+}
+
+fn grep(captures: &[Capture], args: &[String], dir: &Path, now: i64) -> Result<i32, String> {
+    // 筛选条件可以出现在 `--` 之前的任何位置，其余参数原样交给 grep
+    let mut filter = Filter::default();
+    let mut grep_args = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            grep_args.push(arg.clone());
+            grep_args.extend(iter.by_ref().cloned());
+            break;
+        }
+        let (flag, inline) = split_flag(arg);
+        let mut value = || inline.clone().or_else(|| iter.next().cloned()).ok_or(format!("{} 需要参数", flag));
+        if !filter.parse(flag, &mut value)? {
+            grep_args.push(arg.clone());
+        }
+    }
+    if grep_args.is_empty() {
+        return Err("grep 需要搜索模式".to_string());
+    }
+
+    let files: Vec<&Capture> = captures.iter().rev().filter(|capture| filter.matches(capture, now)).collect();
+    if files.is_empty() {
+        eprintln!("📂 没有符合条件的保存文件");
+        return Ok(1);
+    }
+    let original = match resolve_original(&["grep", "ggrep"], Some(&config::global().grep.path)) {
+        Ok(original) => original,
+        Err(e) => {
+            eprintln!("❌ captures: {}", e);
+            return Ok(127);
+        }
+    };
+    // 已压缩的保存文件解压到临时目录后再交给 grep，文件名不变
+    let mut paths: Vec<String> = files.iter().map(|capture| capture.path.display().to_string()).collect();
+    let extracted = match capture::extract_args(&mut paths, dir) {
+        Ok(extracted) => extracted,
+        Err(e) => {
+            eprintln!("❌ captures: 无法解压保存文件: {}", e);
+            return Ok(1);
+        }
+    };
+    if let Some(dir) = extracted.dir() {
+        eprintln!("📦 {} 个已压缩的保存文件解压到 {} 后搜索", extracted.files.len(), dir.display());
+    }
+    let mut cmd = Command::new(&original);
+    guard::mark_child(&mut cmd, "grep");
+    cmd.arg("-H").arg("-n").args(&grep_args).args(&paths);
+    match cmd.status() {
+        Ok(status) => Ok(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("❌ captures: 无法执行 {}: {}", original.display(), e);
+            Ok(1)
+        }
+    }
+}
+
+fn path(captures: &[Capture], args: &[String]) -> Result<i32, String> {
+    if args.len() > 1 {
+        return Err("path 只接受一个引用".to_string());
+    }
+    let reference = args.first().map_or("latest", String::as_str);
+    match find(captures, reference) {
+        Some(capture) => Ok(emit(&format!("{}\n", capture.path.display()))),
+        None => Ok(not_found(reference)),
+    }
+}
+
+fn remove(captures: &[Capture], args: &[String], dir: &Path, now: i64) -> Result<i32, String> {
+    let selection = parse_selection(args)?;
+    if selection.json {
+        return Err("rm 不支持 --json".to_string());
+    }
+    let targets: Vec<&Capture> = if !selection.refs.is_empty() {
+        if !selection.filter.is_empty() || selection.all {
+            return Err("引用不能与筛选条件或 --all 同时使用".to_string());
+        }
+        let mut targets = Vec::new();
+        for reference in &selection.refs {
+            match find(captures, reference) {
+                Some(capture) => targets.push(capture),
+                None => return Ok(not_found(reference)),
+            }
+        }
+        targets
+    } else if selection.all || !selection.filter.is_empty() {
+        captures.iter().filter(|capture| selection.filter.matches(capture, now)).collect()
+    } else {
+        return Err("需要指定要删除的保存文件；删除全部请加 --all".to_string());
+    };
+
+    let bytes = capture::stored_bytes(targets.iter().copied());
+    match capture::remove(&targets, dir) {
+        Ok(removed) => {
+            println!("🗑️  已删除 {} 个保存文件（{}）", removed, human_size(bytes));
+            Ok(0)
+        }
+        Err(e) => {
+            eprintln!("❌ captures: 删除失败: {}", e);
+            Ok(1)
+        }
+    }
+}
+
+fn collect(args: &[String], dir: &Path, now: i64) -> Result<i32, String> {
+    let dry_run = match args {
+        [] => false,
+        [flag] if flag == "--dry-run" => true,
+        _ => return Err(format!("gc 不接受参数 '{}'", args.join(" "))),
+    };
+    let settings = &config::global().capture;
+    let (report, expired) = match capture::gc(dir, settings, now, None, dry_run) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("❌ captures: 清理失败: {}", e);
+            return Ok(1);
+        }
+    };
+
+    let limit = |value: u64, text: String| if value == 0 { "不限".to_string() } else { text };
+    let mut out = format!(
+        "📏 上限: 保留 {}，总大小 {}，每个会话 {} 个文件\n",
+        limit(settings.max_age_secs, human_duration(settings.max_age_secs)),
+        limit(settings.max_total_bytes, human_size(settings.max_total_bytes)),
+        limit(settings.max_files_per_session, settings.max_files_per_session.to_string()),
+    );
+    out.push_str(&format!(
+        "🗜️  压缩: 保存超过 {} 或大于 {} 的内容\n",
+        limit(settings.compress_after_secs, human_duration(settings.compress_after_secs)),
+        limit(settings.compress_above_bytes, human_size(settings.compress_above_bytes)),
+    ));
+    if dry_run {
+        for capture in &expired {
+            out.push_str(&format!("   {}  {}  {}\n", format_time(capture.modified), human_size(capture.bytes), capture.path.display()));
+        }
+    }
+    out.push_str(&format!(
+        "🧹 {} {} 个保存文件（{}），剩余 {} 个（{}）\n",
+        if dry_run { "将清理" } else { "已清理" },
+        report.removed,
+        human_size(report.freed),
+        report.kept,
+        human_size(report.kept_bytes)
+    ));
+    if report.compressed > 0 && dry_run {
+        out.push_str(&format!("📦 将压缩 {} 份内容\n", report.compressed));
+    } else if report.compressed > 0 {
+        out.push_str(&format!("📦 已压缩 {} 份内容，节省 {}\n", report.compressed, human_size(report.saved)));
+    }
+    Ok(emit(&out))
+}
+
+fn parse_selection(args: &[String]) -> Result<Selection, String> {
+    let mut selection = Selection::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let (flag, inline) = split_flag(arg);
+        let mut value = || inline.clone().or_else(|| iter.next().cloned()).ok_or(format!("{} 需要参数", flag));
+        if selection.filter.parse(flag, &mut value)? {
+            continue;
+        }
+        match flag {
+            "--json" => selection.json = true,
+            "--all" => selection.all = true,
+            _ if flag.starts_with("--") => return Err(format!("未知的选项 '{}'", flag)),
+            _ => selection.refs.push(arg.clone()),
+        }
+    }
+    Ok(selection)
+}
+
+/// `--flag=value` 拆成选项和值
+fn split_flag(arg: &str) -> (&str, Option<String>) {
+    match arg.split_once('=') {
+        Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+        _ => (arg, None),
+    }
+}
+
+/// 解析引用：latest、-N、文件名或路径
+fn find<'a>(captures: &'a [Capture], reference: &str) -> Option<&'a Capture> {
+    let back = match reference {
+        "latest" => Some(1),
+        _ => reference.strip_prefix('-').and_then(|n| n.parse::<usize>().ok()),
+    };
+    if let Some(back) = back {
+        return captures.len().checked_sub(back).filter(|_| back > 0).map(|i| &captures[i]);
+    }
+    let wanted = Path::new(reference);
+    let canonical = wanted.canonicalize().ok();
+    // 压缩后的文件也能用保存时的 `.txt` 文件名找到
+    captures.iter().find(|capture| {
+        let saved = capture::saved_path(&capture.path);
+        capture.path == wanted
+            || saved == wanted
+            || capture.path.file_name() == Some(wanted.as_os_str())
+            || saved.file_name() == Some(wanted.as_os_str())
+            || canonical.as_ref().is_some_and(|canonical| capture.path.canonicalize().ok().as_ref() == Some(canonical))
+    })
+}
+
+fn not_found(reference: &str) -> i32 {
+    eprintln!("❌ captures: 找不到保存文件 '{}'（用 cursor-guard captures list 查看）", reference);
+    1
+}
+
+/// 解析行范围：`10:20`、`10:`、`:20`、`-50:`、`7`
+fn parse_range(text: &str) -> Result<(Option<i64>, Option<i64>), String> {
+    let invalid = || format!("行范围无效: {}", text);
+    let bound = |part: &str| -> Result<Option<i64>, String> {
+        match part {
+            "" => Ok(None),
+            _ => part.parse().ok().filter(|&n: &i64| n != 0).map(Some).ok_or_else(invalid),
+        }
+    };
+    match text.split_once(':') {
+        Some((start, end)) => Ok((bound(start)?, bound(end)?)),
+        None => {
+            let line = bound(text)?.ok_or_else(invalid)?;
+            Ok((Some(line), Some(line)))
+        }
+    }
+}
+
+/// 行范围转换为从 1 开始的闭区间，负数从末尾倒数
+fn resolve_range((start, end): (Option<i64>, Option<i64>), total: u64) -> (u64, u64) {
+    let resolve = |n: i64| {
+        if n > 0 {
+            n as u64
+        } else {
+            (total as i64 + n + 1).max(1) as u64
+        }
+    };
+    (start.map_or(1, resolve), end.map_or(u64::MAX, resolve))
+}
+
+/// 解析时长：`30`、`30s`、`10m`、`2h`、`7d`
+fn parse_duration(text: &str) -> Option<i64> {
+    let digits_end = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let number: i64 = text[..digits_end].parse().ok()?;
+    let unit = match &text[digits_end..] {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => return None,
+    };
+    number.checked_mul(unit)
+}
+
+fn parse_duration_arg(flag: &str, raw: &str) -> Result<i64, String> {
+    parse_duration(raw).ok_or(format!("{} 的时长无效: {}", flag, raw))
+}
+
+fn parse_size_arg(flag: &str, raw: &str) -> Result<u64, String> {
+    crate::line_opts::parse_size(raw).ok_or(format!("{} 的大小无效: {}", flag, raw))
+}
+
+/// 占用磁盘的大小，压缩的内容加上标记
+fn stored_size(capture: &Capture) -> String {
+    if capture.compressed() {
+        format!("{} (gz)", human_size(capture.bytes))
+    } else {
+        human_size(capture.bytes)
+    }
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", size, UNITS[unit])
+}
+
+/// 按最大的整除单位显示时长：`604800` → `7d`
+fn human_duration(secs: u64) -> String {
+    [("w", 7 * 86400), ("d", 86400), ("h", 3600), ("m", 60)]
+        .iter()
+        .find(|(_, unit)| secs.is_multiple_of(*unit))
+        .map_or(format!("{}s", secs), |(name, unit)| format!("{}{}", secs / unit, name))
+}
+
+fn format_time(secs: i64) -> String {
+    Local
+        .timestamp_opt(secs, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| secs.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(name: &str, modified: i64, bytes: u64, session: Option<&str>) -> Capture {
+        let meta = session.map(|session| CaptureMeta {
+            path: format!("/tmp/cg/{}", name),
+            created: "2026-01-01T00:00:00.000+08:00".to_string(),
+            command: "tail -n 100".to_string(),
+            pipeline: Some("cargo build 2>&1 | tail -n 5".to_string()),
+            upstream: vec!["cargo build".to_string()],
+            cwd: "/repo".to_string(),
+            agent: Some("cursor".to_string()),
+            session: session.to_string(),
+            bytes,
+            lines: 3,
+            sha256: "00".to_string(),
+        });
+        Capture { path: PathBuf::from(format!("/tmp/cg/{}", name)), modified, bytes, object: None, meta, recording: false }
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_find_references() {
+        let captures = vec![capture("a.txt", 1, 10, None), capture("b.txt", 2, 20, None), capture("c.txt", 3, 30, None)];
+        assert_eq!(find(&captures, "latest").unwrap().path, PathBuf::from("/tmp/cg/c.txt"));
+        assert_eq!(find(&captures, "-1").unwrap().path, PathBuf::from("/tmp/cg/c.txt"));
+        assert_eq!(find(&captures, "-3").unwrap().path, PathBuf::from("/tmp/cg/a.txt"));
+        assert_eq!(find(&captures, "b.txt").unwrap().path, PathBuf::from("/tmp/cg/b.txt"));
+        assert_eq!(find(&captures, "/tmp/cg/a.txt").unwrap().modified, 1);
+        assert!(find(&captures, "-4").is_none());
+        assert!(find(&captures, "-0").is_none());
+        assert!(find(&[], "latest").is_none());
+    }
+
+    #[test]
+    fn test_filter() {
+        let now = 10_000;
+        let old = capture("old.txt", now - 7200, 10, Some("s1"));
+        let new = capture("new.txt", now - 60, 5000, None);
+        let parse = |list: &[&str]| parse_selection(&args(list)).unwrap().filter;
+
+        assert!(parse(&["--session", "s1"]).matches(&old, now));
+        assert!(!parse(&["--session", "s1"]).matches(&new, now));
+        assert!(parse(&["--newer", "10m"]).matches(&new, now));
+        assert!(!parse(&["--newer=10m"]).matches(&old, now));
+        assert!(parse(&["--older", "1h"]).matches(&old, now));
+        assert!(parse(&["--min-size", "1K"]).matches(&new, now));
+        assert!(!parse(&["--max-size", "1K"]).matches(&new, now));
+        // 命令匹配包括管道命令和上游命令
+        assert!(parse(&["--command", "cargo build"]).matches(&old, now));
+        assert!(!parse(&["--command", "cargo"]).matches(&new, now));
+        assert!(parse(&["--command", "new"]).matches(&new, now));
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        let selection = parse_selection(&args(&["-2", "latest", "--all"])).unwrap();
+        assert_eq!(selection.refs, vec!["-2", "latest"]);
+        assert!(selection.all && selection.filter.is_empty());
+        assert!(parse_selection(&args(&["--newer"])).is_err());
+        assert!(parse_selection(&args(&["--newer", "soon"])).is_err());
+        assert!(parse_selection(&args(&["--bogus"])).is_err());
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(parse_range("10:20").unwrap(), (Some(10), Some(20)));
+        assert_eq!(parse_range("-50:").unwrap(), (Some(-50), None));
+        assert_eq!(parse_range("7").unwrap(), (Some(7), Some(7)));
+        assert!(parse_range("0:5").is_err());
+        assert!(parse_range("a:b").is_err());
+
+        assert_eq!(resolve_range((Some(10), Some(20)), 100), (10, 20));
+        assert_eq!(resolve_range((Some(-50), None), 100), (51, u64::MAX));
+        assert_eq!(resolve_range((None, Some(-1)), 100), (1, 100));
+        assert_eq!(resolve_range((Some(-500), None), 100), (1, u64::MAX));
+    }
+
+    #[test]
+    fn test_parse_duration_and_size() {
+        assert_eq!(parse_duration("30"), Some(30));
+        assert_eq!(parse_duration("10m"), Some(600));
+        assert_eq!(parse_duration("7d"), Some(604_800));
+        assert_eq!(parse_duration("1y"), None);
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(human_size(512), "512B");
+        assert_eq!(human_size(1536), "1.5K");
+        assert_eq!(human_size(10 * 1024 * 1024), "10.0M");
+        assert_eq!(human_duration(604_800), "1w");
+        assert_eq!(human_duration(7200), "2h");
+        assert_eq!(human_duration(90), "90s");
+    }
+}
@@ -1,26 +1,29 @@
-use std::env;
-use std::fs;
+pub mod agent;
+pub mod audit;
+pub mod bypass;
+pub mod cache;
+pub mod capture;
+pub mod config;
+pub mod follow;
+pub mod guard;
+pub mod input;
+pub mod mode;
+pub mod pipeline;
+pub mod policy;
+pub mod procfs;
+pub mod proctree;
+pub mod repeat;
+pub mod resolve;
+pub mod session;
+
+use std::ffi::OsStr;
+use std::fs::{self, File};
 use std::process::{Command, Stdio};
 use std::io::{self, Write, Read};
 use std::path::Path;
 
-/// 检测当前进程是否在 Cursor AI 执行环境中
-/// 只在 Cursor AI 执行命令时返回 true，用户手动执行返回 false
-pub fn is_in_cursor() -> bool {
-    // 关键检测：CURSOR_AGENT 环境变量
-    // 经过验证：只有这个变量能准确区分 AI 执行和用户手动执行
-    // - CURSOR_AGENT=1 → 只有 AI 执行时才有
-    // - CURSOR_TRACE_ID → 手动终端也有，不能用来区分
-    if let Ok(agent) = env::var("CURSOR_AGENT") {
-        if agent == "1" {
-            return true;
-        }
-    }
-    
-    // 用户手动在 Cursor 终端执行时，CURSOR_AGENT 不存在
-    false
-}
-
+/// 流式转发管道输入时的缓冲区大小（内存占用上限）
+const TEE_BUFFER_SIZE: usize = 64 * 1024;
 
 /// 生成唯一的临时文件名（确保不会覆盖现有文件）
 fn generate_unique_tmpfile(tmp_dir: &str, prefix: &str) -> String {
@@ -40,40 +43,138 @@ fn generate_unique_tmpfile(tmp_dir: &str, prefix: &str) -> String {
     }
 }
 
-/// 保存管道输入到临时文件（统一接口）
-/// 返回：Ok(Some(文件路径)) 如果保存成功
-///       Ok(None) 如果没有输入
-pub fn save_stdin_to_file(prefix: &str) -> io::Result<Option<String>> {
+/// 在保存目录中创建新的临时文件，文件名中记录检测到的 Agent
+pub(crate) fn create_capture_file(prefix: &str) -> io::Result<(String, File)> {
+    let config = config::global();
+    let tmp_dir = &config.capture.dir;
+    fs::create_dir_all(tmp_dir)?;
+    
+    let prefix = match &config.agent {
+        Some(found) => format!("{}_{}", prefix, found.agent),
+        None => prefix.to_string(),
+    };
+    let tmp_file = generate_unique_tmpfile(tmp_dir, &prefix);
+    let file = File::create(&tmp_file)?;
+    Ok((tmp_file, file))
+}
+
+/// 对文件加排他锁（flock），锁随文件关闭自动释放
+pub(crate) fn lock_exclusive(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: fd 在 file 存活期间有效
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// 文件是否被其他打开的实例加了排他锁（不等待）
+pub(crate) fn is_locked(path: &Path) -> bool {
+    use std::os::unix::io::AsRawFd;
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    // SAFETY: fd 在 file 存活期间有效
+    let failed = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) } != 0;
+    failed && io::Error::last_os_error().raw_os_error() == Some(libc::EWOULDBLOCK)
+}
+
+/// 写保存文件的元数据，返回内容的 SHA-256
+/// 写入失败不影响保存文件本身，只打印警告
+pub(crate) fn record_capture_meta(path: &str, origin: &capture::Origin, command: &str) -> Option<String> {
+    match capture::record(Path::new(path), origin, command) {
+        Ok(meta) => Some(meta.sha256),
+        Err(e) => {
+            eprintln!("⚠️  无法写入保存文件的元数据: {}", e);
+            None
+        }
+    }
+}
+
+/// 按内容存放保存文件，返回相同内容上一次的保存文件（失败只打印警告，保留原文件）
+pub(crate) fn store_capture(path: &str, sha256: Option<&str>) -> Option<capture::Identical> {
+    match capture::store(Path::new(path), sha256?) {
+        Ok(identical) => identical,
+        Err(e) => {
+            eprintln!("⚠️  无法按内容存放保存文件: {}", e);
+            None
+        }
+    }
+}
+
+/// 保存之后按 `[capture]` 的上限清理保存目录，刚保存的文件保留
+pub(crate) fn enforce_capture_limits(saved: &str) {
+    let settings = &config::global().capture;
+    let now = chrono::Local::now().timestamp();
+    if let Err(e) = capture::gc(Path::new(&settings.dir), settings, now, Some(Path::new(saved)), false) {
+        eprintln!("⚠️  清理保存目录失败: {}", e);
+    }
+}
+
+/// 保存下来的管道输入
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captured {
+    /// 保存文件路径
+    pub path: String,
+    /// 字节数
+    pub bytes: u64,
+    /// 行数
+    pub lines: u64,
+    /// 内容的 SHA-256，元数据写入失败时为 None
+    pub sha256: Option<String>,
+}
+
+/// 边读管道输入边保存到临时文件，同时转发给原始命令的标准输入（统一接口）
+/// 原始命令立即开始输出，内存占用不超过 TEE_BUFFER_SIZE
+/// name 是包装命令名（写入重入标记），command 是展示用的命令行，记录在保存文件的元数据中
+/// 返回：(原始命令退出码, 保存的输入)，没有输入或保存失败时为 None
+pub fn tee_stdin_to_command<P: AsRef<OsStr>>(original_cmd: P, name: &str, args: &[String], prefix: &str, command: &str) -> io::Result<(i32, Option<Captured>)> {
+    let (tmp_file, file) = create_capture_file(prefix)?;
+    let origin = capture::Origin::detect();
+    let limit = config::global().capture.max_total_bytes;
+    
     let stdin = io::stdin();
-    let mut handle = stdin.lock();
-    let mut buffer = Vec::new();
+    let outcome = execute_with_tee(original_cmd, name, args, stdin.lock(), &mut capture::QuotaWriter::new(file, limit))?;
     
-    match handle.read_to_end(&mut buffer) {
-        Ok(0) => Ok(None),  // 没有输入
-        Ok(_) => {
-            let tmp_dir = "/tmp/cursor_outputs";
-            fs::create_dir_all(tmp_dir)?;
-            
-            let tmp_file = generate_unique_tmpfile(tmp_dir, prefix);
-            
-            // 写入临时文件
-            fs::write(&tmp_file, &buffer)?;
-            
-            // 输出提示信息
-            print_file_saved_message(&tmp_file);
-            
-            Ok(Some(tmp_file))
+    if let Some(e) = outcome.tee_error {
+        let _ = fs::remove_file(&tmp_file);
+        if e.kind() == io::ErrorKind::QuotaExceeded {
+            capture::print_quota_message(limit);
+            return Ok((outcome.exit_code, None));
         }
-        Err(e) => Err(e),
+        // 保存失败不影响命令本身，只是没有留下文件
+        eprintln!("⚠️  管道输入保存失败（命令已正常执行）: {}", e);
+        return Ok((outcome.exit_code, None));
+    }
+    if outcome.bytes == 0 {
+        let _ = fs::remove_file(&tmp_file);
+        return Ok((outcome.exit_code, None));
     }
+    
+    let sha256 = record_capture_meta(&tmp_file, &origin, command);
+    let identical = store_capture(&tmp_file, sha256.as_deref());
+    print_file_saved_message(&tmp_file, identical.as_ref());
+    enforce_capture_limits(&tmp_file);
+    let captured = Captured { path: tmp_file, bytes: outcome.bytes, lines: outcome.lines, sha256 };
+    Ok((outcome.exit_code, Some(captured)))
 }
 
-/// 打印文件保存提示信息（统一格式）
-pub fn print_file_saved_message(file_path: &str) {
+/// 打印文件保存提示信息（统一格式），identical 为相同内容上一次的保存文件
+pub fn print_file_saved_message(file_path: &str, identical: Option<&capture::Identical>) {
     eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     eprintln!("📋 [Cursor 强制最佳实践] 管道输入已自动保存");
     eprintln!("📁 文件位置: {}", file_path);
+    if let Some(identical) = identical {
+        let age = chrono::Local::now().timestamp() - identical.modified;
+        eprintln!("📎 与 {}保存的内容相同（{}），磁盘上只保存一份", capture::ago(age), identical.path.display());
+    }
     eprintln!("💡 用途: 避免重复运行耗时命令，可直接读取此文件");
+    let name = Path::new(file_path).file_name().map_or(file_path.into(), |name| name.to_string_lossy());
+    let settings = &config::global().capture;
+    if settings.compress_after_secs > 0 || settings.compress_above_bytes > 0 {
+        eprintln!("🗜️  较早或较大的保存文件会压缩为 {}.gz：用 zcat 读取，包装的 grep/head/tail 和 captures show 仍可使用原文件名", name);
+    }
+    eprintln!("🔎 查看: cursor-guard captures show {} --lines 1:100；搜索: cursor-guard captures grep <模式>", name);
     eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
 }
 
@@ -85,31 +186,116 @@ pub fn print_enforcement_message(original_arg: &str, enforced_arg: &str) {
     eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
 }
 
-/// 执行原始命令
-pub fn execute_original_command(original_cmd: &str, args: &[String]) -> io::Result<i32> {
-    let mut cmd = Command::new(original_cmd);
+/// 打印建议信息（warn 模式：按原样执行）
+pub fn print_warning_message(original_arg: &str, suggested_arg: &str) {
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    eprintln!("⚠️  [Cursor 最佳实践建议] {} 参数不足，建议改为 {}", original_arg, suggested_arg);
+    eprintln!("💡 本次按原样执行；输出不够时请直接使用建议的写法，避免反复重跑");
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+}
+
+/// 打印拒绝执行信息（strict 模式）
+pub fn print_refusal_message(original_arg: &str, required_arg: &str) {
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    eprintln!("🚫 [Cursor 强制最佳实践] {} 参数不足，已拒绝执行", original_arg);
+    eprintln!("💡 请改用 {} 重新运行，一次拿到足够的信息", required_arg);
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+}
+
+/// 执行原始命令，name 是包装命令名（写入重入标记）
+pub fn execute_original_command<P: AsRef<OsStr>>(original_cmd: P, name: &str, args: &[String]) -> io::Result<i32> {
+    let mut cmd = Command::new(&original_cmd);
     cmd.args(args);
+    guard::mark_child(&mut cmd, name);
     
     let status = cmd.status()?;
     Ok(status.code().unwrap_or(1))
 }
 
 /// 执行原始命令并传递管道输入
-pub fn execute_with_stdin(original_cmd: &str, args: &[String], stdin_data: &[u8]) -> io::Result<i32> {
-    let mut cmd = Command::new(original_cmd);
+pub fn execute_with_stdin<P: AsRef<OsStr>>(original_cmd: P, name: &str, args: &[String], stdin_data: &[u8]) -> io::Result<i32> {
+    let outcome = execute_with_tee(original_cmd, name, args, stdin_data, &mut io::sink())?;
+    Ok(outcome.exit_code)
+}
+
+/// execute_with_tee 的执行结果
+#[derive(Debug)]
+pub struct TeeOutcome {
+    /// 原始命令退出码
+    pub exit_code: i32,
+    /// 从输入读取的总字节数
+    pub bytes: u64,
+    /// 从输入读取的总行数（换行符个数）
+    pub lines: u64,
+    /// 写入 tee 时遇到的错误（出错后不再写入 tee，但继续转发给命令）
+    pub tee_error: Option<io::Error>,
+}
+
+/// 执行原始命令，把 input 流式写入命令的标准输入，同时复制一份到 tee
+///
+/// 命令提前退出（例如 head 读够了行数）时继续把剩余输入写入 tee，
+/// 保证保存下来的是完整输入
+pub fn execute_with_tee<P, R, W>(original_cmd: P, name: &str, args: &[String], mut input: R, tee: &mut W) -> io::Result<TeeOutcome>
+where
+    P: AsRef<OsStr>,
+    R: Read,
+    W: Write + ?Sized,
+{
+    let mut cmd = Command::new(&original_cmd);
+    guard::mark_child(&mut cmd, name);
     cmd.args(args)
         .stdin(Stdio::piped())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit());
     
     let mut child = cmd.spawn()?;
+    let mut child_stdin = child.stdin.take();
+    let mut buffer = vec![0u8; TEE_BUFFER_SIZE];
+    let mut bytes = 0u64;
+    let mut lines = 0u64;
+    let mut tee_error = None;
     
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(stdin_data)?;
+    loop {
+        let n = match input.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                drop(child_stdin);
+                let _ = child.wait();
+                return Err(e);
+            }
+        };
+        bytes += n as u64;
+        let chunk = &buffer[..n];
+        lines += chunk.iter().filter(|&&b| b == b'\n').count() as u64;
+        
+        if tee_error.is_none() {
+            if let Err(e) = tee.write_all(chunk) {
+                tee_error = Some(e);
+            }
+        }
+        // 命令已关闭标准输入（EPIPE），之后只写 tee
+        if let Some(stdin) = child_stdin.as_mut() {
+            if stdin.write_all(chunk).is_err() {
+                child_stdin = None;
+            }
+        }
+        if child_stdin.is_none() && tee_error.is_some() {
+            break;
+        }
     }
     
+    // 关闭标准输入，让命令看到 EOF
+    drop(child_stdin);
     let status = child.wait()?;
-    Ok(status.code().unwrap_or(1))
+    if tee_error.is_none() {
+        if let Err(e) = tee.flush() {
+            tee_error = Some(e);
+        }
+    }
+    
+    Ok(TeeOutcome { exit_code: status.code().unwrap_or(1), bytes, lines, tee_error })
 }
 
 // ============================================================================
@@ -122,49 +308,6 @@ mod tests {
     use std::fs;
     use std::path::Path;
 
-    #[test]
-    fn test_is_in_cursor_with_cursor_agent() {
-        // 清理环境
-        std::env::remove_var("CURSOR_AGENT");
-        
-        // 测试 CURSOR_AGENT=1
-        std::env::set_var("CURSOR_AGENT", "1");
-        assert!(is_in_cursor(), "应该检测到 CURSOR_AGENT=1");
-        
-        // 清理
-        std::env::remove_var("CURSOR_AGENT");
-    }
-
-    #[test]
-    fn test_is_not_in_cursor_manual_terminal() {
-        // 清理 CURSOR_AGENT
-        std::env::remove_var("CURSOR_AGENT");
-        
-        // 即使有 CURSOR_TRACE_ID（手动终端也有），也不应该触发
-        std::env::set_var("CURSOR_TRACE_ID", "test-trace-id");
-        std::env::set_var("TERM_PROGRAM", "vscode");
-        assert!(!is_in_cursor(), "用户手动在 Cursor 终端执行不应该触发");
-        
-        // 清理
-        std::env::remove_var("CURSOR_TRACE_ID");
-        std::env::remove_var("TERM_PROGRAM");
-    }
-    
-    #[test]
-    fn test_cursor_agent_only_detection() {
-        // 验证：只有 CURSOR_AGENT=1 才会触发
-        std::env::remove_var("CURSOR_AGENT");
-        
-        // 有其他 Cursor 变量但没有 CURSOR_AGENT → 不触发
-        std::env::set_var("CURSOR_TRACE_ID", "xxx");
-        std::env::set_var("VSCODE_IPC_HOOK_CLI", "/tmp/cursor.sock");
-        assert!(!is_in_cursor(), "没有 CURSOR_AGENT=1 不应该触发");
-        
-        // 清理
-        std::env::remove_var("CURSOR_TRACE_ID");
-        std::env::remove_var("VSCODE_IPC_HOOK_CLI");
-    }
-
     #[test]
     fn test_generate_unique_tmpfile() {
         let tmp_dir = "/tmp/cursor_test";
@@ -213,7 +356,7 @@ mod tests {
     #[test]
     fn test_print_file_saved_message() {
         // 这个测试只是确保函数不会 panic
-        print_file_saved_message("/tmp/test.txt");
+        print_file_saved_message("/tmp/test.txt", None);
     }
 
     #[test]
@@ -222,11 +365,42 @@ mod tests {
         print_enforcement_message("tail -5", "tail -100");
     }
 
+    #[test]
+    fn test_print_warning_and_refusal_messages() {
+        // 这个测试只是确保函数不会 panic
+        print_warning_message("grep", "grep -C 20");
+        print_refusal_message("head -1", "head -n 100");
+    }
+
+    #[test]
+    fn test_execute_with_tee_copies_all_input() {
+        // true 不读标准输入直接退出，tee 仍然应该拿到完整输入
+        let input = vec![b'x'; TEE_BUFFER_SIZE * 3 + 17];
+        let mut tee = Vec::new();
+        let outcome = execute_with_tee("true", "true", &[], &input[..], &mut tee).unwrap();
+        assert_eq!(outcome.exit_code, 0);
+        assert_eq!(outcome.bytes, input.len() as u64);
+        assert_eq!(tee, input);
+        assert!(outcome.tee_error.is_none());
+    }
+
+    #[test]
+    fn test_execute_with_tee_feeds_command() {
+        // 命令能从标准输入读到数据：grep -q 找到匹配返回 0，否则返回 1
+        let args = vec!["-q".to_string(), "needle".to_string()];
+        let mut tee = Vec::new();
+        let found = execute_with_tee("grep", "grep", &args, &b"hay\nneedle\n"[..], &mut tee).unwrap();
+        assert_eq!(found.exit_code, 0);
+        assert_eq!(found.lines, 2);
+        let missing = execute_with_tee("grep", "grep", &args, &b"hay\n"[..], &mut io::sink()).unwrap();
+        assert_eq!(missing.exit_code, 1);
+    }
+
     #[test]
     fn test_execute_original_command() {
         // 测试执行简单命令
         let args = vec!["test".to_string()];
-        let result = execute_original_command("echo", &args);
+        let result = execute_original_command("echo", "echo", &args);
         assert!(result.is_ok(), "应该能够执行 echo 命令");
         assert_eq!(result.unwrap(), 0, "echo 命令应该返回 0");
     }
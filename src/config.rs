@@ -0,0 +1,807 @@
+//! 分层 TOML 配置
+//!
+//! 优先级从低到高：
+//! 1. 内置默认值
+//! 2. 系统配置 `/etc/cursor-guard/config.toml`
+//! 3. 用户配置 `~/.config/cursor-guard/config.toml`（遵循 `XDG_CONFIG_HOME`）
+//! 4. 仓库配置：从当前目录向上查找最近的 `.cursorguard.toml`（到 git 仓库根目录或 `$HOME` 为止）
+//! 5. 检测到的 Agent 对应的 `[profiles.<名称>]`，例如 `[profiles.claude-code.tail]`
+//! 6. 环境变量 `CURSOR_GUARD_<段>_<键>`，例如 `CURSOR_GUARD_TAIL_MIN_LINES=200`
+
+use crate::agent::{self, AgentSignals, Detection};
+use crate::mode::Mode;
+use crate::procfs::ProcFs;
+use crate::{proctree, session};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::OnceLock;
+use toml::{Table, Value};
+
+/// 环境变量覆盖的前缀
+pub const ENV_PREFIX: &str = "CURSOR_GUARD_";
+
+/// 系统级配置文件
+pub const SYSTEM_CONFIG: &str = "/etc/cursor-guard/config.toml";
+
+/// 仓库级配置文件名
+pub const REPO_CONFIG_NAME: &str = ".cursorguard.toml";
+
+/// 仓库配置不能设置的项：执行哪个程序、往哪里写文件只能由系统、用户配置或环境变量决定，
+/// 否则克隆一个不可信的仓库后运行 grep 就会执行仓库指定的程序
+const REPO_RESTRICTED: &[(&str, &str)] =
+    &[("grep", "path"), ("tail", "path"), ("head", "path"), ("capture", "dir"), ("audit", "path")];
+
+/// 全部可配置项
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// 全局强制模式，各包装器可单独覆盖
+    pub mode: Mode,
+    pub guard: GuardConfig,
+    pub capture: CaptureConfig,
+    pub audit: AuditConfig,
+    pub grep: GrepConfig,
+    pub tail: LineConfig,
+    pub head: LineConfig,
+    pub follow: FollowConfig,
+    pub repeat: RepeatConfig,
+    pub cache: CacheConfig,
+    pub pipeline: PipelineConfig,
+    pub process_tree: ProcessTreeConfig,
+    /// 给内置 Agent 检测器补充信号，或注册新的 Agent
+    pub agents: BTreeMap<String, AgentSignals>,
+    /// 按 Agent 名称叠加的配置，内容与整个配置文件相同
+    pub profiles: BTreeMap<String, Table>,
+    /// 检测到的 Agent（运行时结果，不来自配置文件）
+    #[serde(skip)]
+    pub agent: Option<Detection>,
+}
+
+/// 递归保护设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuardConfig {
+    /// 包装器最多嵌套的层数
+    pub max_depth: u32,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        GuardConfig { max_depth: 8 }
+    }
+}
+
+/// 管道输入保存设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptureConfig {
+    /// 保存目录
+    pub dir: String,
+    /// 保存文件最多保留的秒数，0 表示不限
+    pub max_age_secs: u64,
+    /// 保存目录的总大小上限（字节），0 表示不限；单个输入超过该值时不保存
+    pub max_total_bytes: u64,
+    /// 每个会话最多保留的文件数，0 表示不限
+    pub max_files_per_session: u64,
+    /// 保存超过多少秒后压缩，0 表示不按时间压缩
+    pub compress_after_secs: u64,
+    /// 超过多少字节时压缩，0 表示不按大小压缩
+    pub compress_above_bytes: u64,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        CaptureConfig {
+            dir: "/tmp/cursor_outputs".to_string(),
+            max_age_secs: 7 * 24 * 3600,
+            max_total_bytes: 1024 * 1024 * 1024,
+            max_files_per_session: 100,
+            compress_after_secs: 3600,
+            compress_above_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// 审计日志设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// 是否记录 Agent 的每次调用
+    pub enabled: bool,
+    /// 日志文件路径，为空时使用状态目录下的 audit.jsonl
+    pub path: String,
+    /// 日志超过该字节数时轮转为 `<path>.1`（只保留一份旧日志），0 表示不轮转
+    pub max_bytes: u64,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig { enabled: true, path: String::new(), max_bytes: 10 * 1024 * 1024 }
+    }
+}
+
+/// grep 强制规则设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GrepConfig {
+    /// 未指定上下文时添加的 -C 行数
+    pub context: u32,
+    /// 原始 grep 路径，为空时在 PATH 中查找
+    pub path: String,
+    /// 输出接到下游程序时的处理方式
+    pub downstream: Downstream,
+    /// 强制模式，inherit 表示使用全局设置
+    pub mode: Mode,
+}
+
+impl Default for GrepConfig {
+    fn default() -> Self {
+        GrepConfig { context: 20, path: String::new(), downstream: Downstream::Inherit, mode: Mode::Inherit }
+    }
+}
+
+/// tail/head 强制规则设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LineConfig {
+    /// 最少输出行数
+    pub min_lines: u32,
+    /// 使用 -c 按字节输出时的最少字节数
+    pub min_bytes: u64,
+    /// 原始命令路径，为空时在 PATH 中查找
+    pub path: String,
+    /// 输出接到下游程序时的处理方式
+    pub downstream: Downstream,
+    /// 强制模式，inherit 表示使用全局设置
+    pub mode: Mode,
+}
+
+impl Default for LineConfig {
+    fn default() -> Self {
+        LineConfig {
+            min_lines: 100,
+            min_bytes: 4096,
+            path: String::new(),
+            downstream: Downstream::Inherit,
+            mode: Mode::Inherit,
+        }
+    }
+}
+
+/// tail -f 跟随模式的预算
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FollowConfig {
+    /// 最长跟随秒数，0 表示不限
+    pub max_secs: u64,
+    /// 最多输出行数，0 表示不限
+    pub max_lines: u64,
+    /// 结束后后台继续记录文件新内容的秒数，0 表示不记录
+    pub record_secs: u64,
+}
+
+impl Default for FollowConfig {
+    fn default() -> Self {
+        FollowConfig { max_secs: 30, max_lines: 2000, record_secs: 600 }
+    }
+}
+
+/// 重复命令检测设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepeatConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 时间窗口内运行多少次（含本次）时提示，0 表示不提示
+    pub threshold: u32,
+    /// 时间窗口（秒）
+    pub window_secs: u64,
+    /// strict 模式下达到阈值时是否拒绝执行（只对不读取管道输入的命令生效）
+    pub refuse: bool,
+}
+
+impl Default for RepeatConfig {
+    fn default() -> Self {
+        RepeatConfig { enabled: true, threshold: 3, window_secs: 600, refuse: true }
+    }
+}
+
+/// 结果缓存设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// 是否启用（默认关闭）
+    pub enabled: bool,
+    /// 缓存有效秒数
+    pub max_age_secs: u64,
+    /// 输出超过该字节数时不缓存
+    pub max_bytes: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig { enabled: false, max_age_secs: 300, max_bytes: 10 * 1024 * 1024 }
+    }
+}
+
+/// 管道相关设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineConfig {
+    /// 输出接到下游程序（`cmd | wc -l`、`$(cmd)`）时的默认处理方式
+    pub downstream: Downstream,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig { downstream: Downstream::Capture }
+    }
+}
+
+/// 进程树检测设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProcessTreeConfig {
+    /// 是否启用（手动在 Cursor 终端执行时祖先进程同样包含 Cursor，默认关闭）
+    pub enabled: bool,
+    /// 命中时记录的 Agent 名称
+    pub agent: String,
+    /// 最多向上检查的父进程层数
+    pub max_depth: u32,
+    /// 匹配进程名或命令行的模式（不区分大小写的子串）
+    pub patterns: Vec<String>,
+}
+
+impl Default for ProcessTreeConfig {
+    fn default() -> Self {
+        ProcessTreeConfig {
+            enabled: false,
+            agent: "cursor".to_string(),
+            max_depth: 10,
+            patterns: vec!["Cursor".to_string(), "vscode".to_string()],
+        }
+    }
+}
+
+/// 输出接到下游程序时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Downstream {
+    /// 使用 `[pipeline]` 中的设置（只用于各包装器）
+    Inherit,
+    /// 照常改写参数
+    Enforce,
+    /// 不改写参数，只保存管道输入
+    Capture,
+    /// 完全不干预，直接执行原始命令
+    Passthrough,
+}
+
+impl std::fmt::Display for Downstream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Downstream::Inherit => "inherit",
+            Downstream::Enforce => "enforce",
+            Downstream::Capture => "capture",
+            Downstream::Passthrough => "passthrough",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Downstream {
+    /// 包装器的设置为 inherit 时使用全局设置
+    pub fn or(self, global: Downstream) -> Downstream {
+        match (self, global) {
+            (Downstream::Inherit, Downstream::Inherit) => Downstream::Capture,
+            (Downstream::Inherit, global) => global,
+            (own, _) => own,
+        }
+    }
+}
+
+/// 当前进程的配置（首次调用时加载，之后复用）
+pub fn global() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(load)
+}
+
+/// 按优先级加载所有配置层，检测 Agent 并叠加对应的 profile
+pub fn load() -> Config {
+    let mut layers = Vec::new();
+    for path in config_paths() {
+        if let Some(mut table) = read_layer(&path) {
+            if path.file_name().is_some_and(|name| name == REPO_CONFIG_NAME) {
+                for key in restrict_repo_layer(&mut table) {
+                    eprintln!("⚠️  [cursor-guard] 仓库配置 {} 不能设置 {}，已忽略（请写在用户配置或环境变量中）", path.display(), key);
+                }
+            }
+            layers.push(table);
+        }
+    }
+    let vars: Vec<(String, String)> = env::vars().collect();
+    let base = Config::from_layers(layers.clone(), vars.clone());
+
+    let proc = ProcFs::system();
+    let detection = agent::detect(&base.agents, |name| env::var(name).ok(), &proc, process::id()).or_else(|| {
+        let tree = &base.process_tree;
+        tree.enabled.then(|| proctree::detect_cached(tree, &proc, process::id(), &session::dir()))?
+    });
+    let mut config = match &detection {
+        Some(found) if base.profiles.contains_key(&found.agent) => {
+            Config::from_layers_for_agent(layers, vars, Some(&found.agent))
+        }
+        _ => base,
+    };
+    config.agent = detection;
+    config
+}
+
+/// 所有可能存在的配置文件路径（按优先级从低到高）
+pub fn config_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(SYSTEM_CONFIG)];
+    if let Some(user) = user_config_path() {
+        paths.push(user);
+    }
+    if let Some(repo) = env::current_dir().ok().and_then(|cwd| find_repo_config(&cwd)) {
+        paths.push(repo);
+    }
+    paths
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let base = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+    };
+    Some(base.join("cursor-guard").join("config.toml"))
+}
+
+/// 从 start 开始向上查找最近的仓库配置
+///
+/// 查到 git 工作区根目录（含 `.git` 的目录）或 `$HOME` 为止，不会读到仓库或家目录之外；
+/// 不属于当前用户或 root 的文件会被忽略，避免共享目录中其他用户放置的配置生效。
+pub fn find_repo_config(start: &Path) -> Option<PathBuf> {
+    let home = env::var_os("HOME").filter(|home| !home.is_empty()).map(PathBuf::from);
+    // SAFETY: getuid 总是成功，没有副作用
+    let uid = unsafe { libc::getuid() };
+    find_repo_config_in(start, home.as_deref(), uid)
+}
+
+fn find_repo_config_in(start: &Path, home: Option<&Path>, uid: u32) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        let path = dir.join(REPO_CONFIG_NAME);
+        if let Ok(meta) = fs::metadata(&path) {
+            if meta.is_file() && trusted_owner(meta.uid(), uid) {
+                return Some(path);
+            }
+            if meta.is_file() {
+                eprintln!("⚠️  [cursor-guard] 仓库配置 {} 不属于当前用户或 root，已忽略", path.display());
+            }
+        }
+        if dir.join(".git").exists() || home == Some(dir) {
+            break;
+        }
+    }
+    None
+}
+
+/// 只信任当前用户或 root 拥有的配置文件
+fn trusted_owner(owner: u32, uid: u32) -> bool {
+    owner == uid || owner == 0
+}
+
+/// 去掉仓库配置（包括其中的 profiles）里不允许设置的项，返回被去掉的键
+pub fn restrict_repo_layer(table: &mut Table) -> Vec<String> {
+    let mut removed = Vec::new();
+    let mut strip = |table: &mut Table, prefix: &str| {
+        for (section, field) in REPO_RESTRICTED {
+            if let Some(Value::Table(section_table)) = table.get_mut(*section) {
+                if section_table.remove(*field).is_some() {
+                    removed.push(format!("{}{}.{}", prefix, section, field));
+                }
+            }
+        }
+    };
+    strip(table, "");
+    if let Some(Value::Table(profiles)) = table.get_mut("profiles") {
+        for (name, profile) in profiles.iter_mut() {
+            if let Value::Table(profile) = profile {
+                strip(profile, &format!("profiles.{}.", name));
+            }
+        }
+    }
+    removed
+}
+
+/// 读取一层配置，文件不存在时返回 None，内容无效时警告并忽略
+fn read_layer(path: &Path) -> Option<Table> {
+    let content = fs::read_to_string(path).ok()?;
+    match parse_layer(&content) {
+        Ok(table) => Some(table),
+        Err(e) => {
+            eprintln!("⚠️  [cursor-guard] 配置文件 {} 无效，已忽略: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// 解析并校验一层配置
+fn parse_layer(content: &str) -> Result<Table, String> {
+    let table: Table = content.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let config: Config = Value::Table(table.clone()).try_into().map_err(|e| e.to_string())?;
+    for (name, profile) in config.profiles {
+        Value::Table(profile)
+            .try_into::<Config>()
+            .map_err(|e| format!("profiles.{}: {}", name, e))?;
+    }
+    Ok(table)
+}
+
+impl Config {
+    /// 依次叠加各配置层，最后应用环境变量覆盖
+    pub fn from_layers<I>(layers: Vec<Table>, vars: I) -> Config
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        Config::from_layers_for_agent(layers, vars, None)
+    }
+
+    /// 同 [`Config::from_layers`]，并在环境变量之前叠加 agent 对应的 profile
+    pub fn from_layers_for_agent<I>(layers: Vec<Table>, vars: I, agent: Option<&str>) -> Config
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let defaults = Config::default().to_table();
+        let mut merged = defaults.clone();
+        // 每一步合并后都校验，无效时只丢弃这一层（或这一个环境变量），其他配置照常生效
+        for (i, layer) in layers.into_iter().enumerate() {
+            if let Err(e) = merge_valid(&mut merged, layer) {
+                eprintln!("⚠️  [cursor-guard] 第 {} 层配置无效，已忽略: {}", i + 1, e);
+            }
+        }
+        let profile = agent.and_then(|name| Some((name, merged.get("profiles")?.get(name)?.as_table().cloned()?)));
+        if let Some((name, profile)) = profile {
+            if let Err(e) = merge_valid(&mut merged, profile) {
+                eprintln!("⚠️  [cursor-guard] profiles.{} 无效，已忽略: {}", name, e);
+            }
+        }
+        apply_env_overrides(&mut merged, &defaults, vars);
+
+        match Value::Table(merged).try_into() {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("⚠️  [cursor-guard] 配置无效，使用默认值: {}", e);
+                Config::default()
+            }
+        }
+    }
+
+    fn to_table(&self) -> Table {
+        Table::try_from(self).expect("默认配置总能序列化")
+    }
+}
+
+/// 把 overlay 深度合并到 base
+fn merge_table(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_child)), Value::Table(child)) => merge_table(base_child, child),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// 把 overlay 合并到 base，合并后不是有效配置时保持 base 不变并返回错误
+fn merge_valid(base: &mut Table, overlay: Table) -> Result<(), String> {
+    let mut candidate = base.clone();
+    merge_table(&mut candidate, overlay);
+    Value::Table(candidate.clone()).try_into::<Config>().map_err(|e| e.to_string())?;
+    *base = candidate;
+    Ok(())
+}
+
+/// 应用 CURSOR_GUARD_* 环境变量，值按默认配置中对应项的类型解析
+///
+/// 无效的变量（类型不对，或不是允许的取值）只忽略这一个
+fn apply_env_overrides<I>(table: &mut Table, defaults: &Table, vars: I)
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    for (name, raw) in vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let key = key.to_ascii_lowercase();
+        let Some((section, field)) = locate_key(defaults, &key) else {
+            continue;
+        };
+
+        let default = match &section {
+            Some(section) => defaults.get(section).and_then(|t| t.get(&field)),
+            None => defaults.get(&field),
+        };
+        let Some(value) = parse_env_value(&raw, default) else {
+            eprintln!("⚠️  [cursor-guard] 环境变量 {}={} 无效，已忽略", name, raw);
+            continue;
+        };
+
+        let mut overlay = Table::new();
+        match section {
+            Some(section) => {
+                overlay.insert(section, Value::Table(Table::from_iter([(field, value)])));
+            }
+            None => {
+                overlay.insert(field, value);
+            }
+        }
+        if merge_valid(table, overlay).is_err() {
+            eprintln!("⚠️  [cursor-guard] 环境变量 {}={} 无效，已忽略", name, raw);
+        }
+    }
+}
+
+/// 把 `tail_min_lines` 这样的键拆成 (段, 字段)，顶层字段返回 (None, 字段)
+fn locate_key(defaults: &Table, key: &str) -> Option<(Option<String>, String)> {
+    if let Some(value) = defaults.get(key) {
+        if !value.is_table() {
+            return Some((None, key.to_string()));
+        }
+    }
+    defaults.iter().find_map(|(section, value)| {
+        let fields = value.as_table()?;
+        let field = key.strip_prefix(section.as_str())?.strip_prefix('_')?;
+        fields
+            .contains_key(field)
+            .then(|| (Some(section.clone()), field.to_string()))
+    })
+}
+
+/// 按默认值的类型解析环境变量；没有默认值的字段按字符串处理
+fn parse_env_value(raw: &str, default: Option<&Value>) -> Option<Value> {
+    match default {
+        Some(Value::Integer(_)) => raw.trim().parse().ok().map(Value::Integer),
+        Some(Value::Float(_)) => raw.trim().parse().ok().map(Value::Float),
+        Some(Value::Boolean(_)) => match raw.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Some(Value::Boolean(true)),
+            "0" | "false" | "no" | "off" | "" => Some(Value::Boolean(false)),
+            _ => None,
+        },
+        Some(Value::Array(_)) => Some(Value::Array(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| Value::String(s.to_string()))
+                .collect(),
+        )),
+        _ => Some(Value::String(raw.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(content: &str) -> Table {
+        parse_layer(content).unwrap()
+    }
+
+    fn no_env() -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::from_layers(Vec::new(), no_env());
+        assert_eq!(config.tail.min_lines, 100);
+        assert_eq!(config.head.min_lines, 100);
+        assert_eq!(config.grep.context, 20);
+        assert_eq!(config.capture.dir, "/tmp/cursor_outputs");
+        assert_eq!(config.capture.max_files_per_session, 100);
+        assert_eq!(config.capture.compress_after_secs, 3600);
+    }
+
+    #[test]
+    fn test_later_layers_win() {
+        let system = layer("[tail]\nmin_lines = 50\n[grep]\ncontext = 5\n");
+        let repo = layer("[tail]\nmin_lines = 300\n");
+        let config = Config::from_layers(vec![system, repo], no_env());
+        assert_eq!(config.tail.min_lines, 300);
+        assert_eq!(config.grep.context, 5);
+        assert_eq!(config.head.min_lines, 100);
+    }
+
+    #[test]
+    fn test_env_overrides_files() {
+        let repo = layer("[head]\nmin_lines = 30\n");
+        let env = vec![
+            ("CURSOR_GUARD_HEAD_MIN_LINES".to_string(), "40".to_string()),
+            ("CURSOR_GUARD_CAPTURE_DIR".to_string(), "/tmp/cg".to_string()),
+            ("CURSOR_AGENT".to_string(), "1".to_string()),
+        ];
+        let config = Config::from_layers(vec![repo], env);
+        assert_eq!(config.head.min_lines, 40);
+        assert_eq!(config.capture.dir, "/tmp/cg");
+    }
+
+    #[test]
+    fn test_path_override_from_env() {
+        let env = vec![("CURSOR_GUARD_GREP_PATH".to_string(), "/opt/homebrew/bin/ggrep".to_string())];
+        let config = Config::from_layers(Vec::new(), env);
+        assert_eq!(config.grep.path, "/opt/homebrew/bin/ggrep");
+        assert_eq!(config.tail.path, "");
+    }
+
+    #[test]
+    fn test_invalid_env_value_is_ignored() {
+        let env = vec![("CURSOR_GUARD_GREP_CONTEXT".to_string(), "many".to_string())];
+        let config = Config::from_layers(Vec::new(), env);
+        assert_eq!(config.grep.context, 20);
+    }
+
+    #[test]
+    fn test_bad_env_value_keeps_other_layers() {
+        let user = layer("mode = \"off\"\n[agents.my-agent]\nenv = [\"MY_AGENT\"]\n");
+        let repo = layer("[tail]\nmin_lines = 7\n");
+        let env = vec![
+            ("CURSOR_GUARD_MODE".to_string(), "bogus".to_string()),
+            ("CURSOR_GUARD_CAPTURE_MAX_AGE_SECS".to_string(), "-5".to_string()),
+            ("CURSOR_GUARD_HEAD_MIN_LINES".to_string(), "40".to_string()),
+        ];
+        let config = Config::from_layers(vec![user, repo], env);
+        assert_eq!(config.mode, Mode::Off);
+        assert!(config.agents.contains_key("my-agent"));
+        assert_eq!(config.capture.max_age_secs, CaptureConfig::default().max_age_secs);
+        assert_eq!((config.tail.min_lines, config.head.min_lines), (7, 40));
+    }
+
+    #[test]
+    fn test_downstream_per_wrapper() {
+        let repo = layer("[pipeline]\ndownstream = \"passthrough\"\n[grep]\ndownstream = \"enforce\"\n");
+        let env = vec![("CURSOR_GUARD_HEAD_DOWNSTREAM".to_string(), "capture".to_string())];
+        let config = Config::from_layers(vec![repo], env);
+        let global = config.pipeline.downstream;
+        assert_eq!(global, Downstream::Passthrough);
+        assert_eq!(config.grep.downstream.or(global), Downstream::Enforce);
+        assert_eq!(config.head.downstream.or(global), Downstream::Capture);
+        assert_eq!(config.tail.downstream.or(global), Downstream::Passthrough);
+        assert!(parse_layer("[pipeline]\ndownstream = \"sometimes\"\n").is_err());
+    }
+
+    #[test]
+    fn test_mode_global_and_per_wrapper() {
+        let user = layer("mode = \"warn\"\n[tail]\nmode = \"strict\"\n");
+        let env = vec![("CURSOR_GUARD_GREP_MODE".to_string(), "observe".to_string())];
+        let config = Config::from_layers(vec![user], env);
+        assert_eq!(config.mode, Mode::Warn);
+        assert_eq!(config.tail.mode.or(config.mode), Mode::Strict);
+        assert_eq!(config.grep.mode.or(config.mode), Mode::Observe);
+        assert_eq!(config.head.mode.or(config.mode), Mode::Warn);
+
+        let env = vec![("CURSOR_GUARD_MODE".to_string(), "off".to_string())];
+        assert_eq!(Config::from_layers(Vec::new(), env).mode, Mode::Off);
+    }
+
+    #[test]
+    fn test_agent_profile_overlay() {
+        let user = layer(
+            "[tail]\nmin_lines = 50\n[profiles.claude-code]\nmode = \"warn\"\n[profiles.claude-code.tail]\nmin_lines = 500\n",
+        );
+        let env = vec![("CURSOR_GUARD_TAIL_MIN_LINES".to_string(), "700".to_string())];
+
+        let plain = Config::from_layers(vec![user.clone()], no_env());
+        assert_eq!(plain.tail.min_lines, 50);
+        assert_eq!(plain.mode, Mode::Enforce);
+
+        let claude = Config::from_layers_for_agent(vec![user.clone()], no_env(), Some("claude-code"));
+        assert_eq!(claude.tail.min_lines, 500);
+        assert_eq!(claude.mode, Mode::Warn);
+
+        // 环境变量仍然优先于 profile
+        let claude = Config::from_layers_for_agent(vec![user.clone()], env, Some("claude-code"));
+        assert_eq!(claude.tail.min_lines, 700);
+
+        let cursor = Config::from_layers_for_agent(vec![user], no_env(), Some("cursor"));
+        assert_eq!(cursor.tail.min_lines, 50);
+    }
+
+    #[test]
+    fn test_agent_signals_from_config() {
+        let user = layer("[agents.windsurf]\nenv = [\"WINDSURF_CASCADE=1\"]\n");
+        let config = Config::from_layers(vec![user], no_env());
+        assert_eq!(config.agents["windsurf"].env, vec!["WINDSURF_CASCADE=1"]);
+    }
+
+    #[test]
+    fn test_invalid_profile_is_rejected() {
+        assert!(parse_layer("[profiles.codex.tail]\nmin_line = 5\n").is_err());
+        assert!(parse_layer("[agents.codex]\nenv = \"X\"\n").is_err());
+    }
+
+    #[test]
+    fn test_process_tree_from_env() {
+        let env = vec![
+            ("CURSOR_GUARD_PROCESS_TREE_ENABLED".to_string(), "1".to_string()),
+            ("CURSOR_GUARD_PROCESS_TREE_PATTERNS".to_string(), "extensionHost, Cursor Helper".to_string()),
+        ];
+        let config = Config::from_layers(Vec::new(), env);
+        assert!(config.process_tree.enabled);
+        assert_eq!(config.process_tree.patterns, vec!["extensionHost", "Cursor Helper"]);
+        assert_eq!(config.process_tree.max_depth, 10);
+    }
+
+    #[test]
+    fn test_invalid_layer_is_rejected() {
+        assert!(parse_layer("[tail]\nmin_lines = \"lots\"\n").is_err());
+        assert!(parse_layer("[tail]\nmin_line = 5\n").is_err());
+    }
+
+    #[test]
+    fn test_repo_layer_cannot_set_paths() {
+        let user = layer("[grep]\npath = \"/usr/bin/grep\"\n[capture]\ndir = \"/tmp/user_outputs\"\n");
+        let mut repo = layer(
+            "[grep]\npath = \"./evil\"\ncontext = 5\n[capture]\ndir = \".\"\nmax_age_secs = 60\n\
+             [audit]\npath = \"audit.jsonl\"\n[profiles.cursor.tail]\npath = \"./evil\"\nmin_lines = 50\n",
+        );
+        let removed = restrict_repo_layer(&mut repo);
+        assert_eq!(removed, vec!["grep.path", "capture.dir", "audit.path", "profiles.cursor.tail.path"]);
+        let config = Config::from_layers_for_agent(vec![user, repo], no_env(), Some("cursor"));
+        assert_eq!(config.grep.path, "/usr/bin/grep");
+        assert_eq!(config.capture.dir, "/tmp/user_outputs");
+        assert_eq!(config.audit.path, "");
+        assert_eq!(config.tail.path, "");
+        // 其他设置照常生效
+        assert_eq!((config.grep.context, config.capture.max_age_secs, config.tail.min_lines), (5, 60, 50));
+    }
+
+    #[test]
+    fn test_find_repo_config_walks_up() {
+        let root = Path::new("/tmp/cursor_test_config");
+        let nested = root.join("a/b/c");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join("a").join(REPO_CONFIG_NAME), "[tail]\nmin_lines = 7\n").unwrap();
+
+        let found = find_repo_config(&nested).unwrap();
+        assert_eq!(found, root.join("a").join(REPO_CONFIG_NAME));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_find_repo_config_stops_at_repo_root_and_home() {
+        let root = std::env::temp_dir().join(format!("cursor_test_config_bounds_{}", process::id()));
+        let repo = root.join("repo");
+        let home = root.join("home");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::create_dir_all(home.join("project")).unwrap();
+        fs::write(root.join(REPO_CONFIG_NAME), "[tail]\nmin_lines = 7\n").unwrap();
+        let uid = fs::metadata(&root).unwrap().uid();
+
+        // 仓库之外、家目录之外的配置不会被读到
+        assert_eq!(find_repo_config_in(&repo.join("src"), None, uid), None);
+        assert_eq!(find_repo_config_in(&home.join("project"), Some(&home), uid), None);
+        // 没有边界时仍然向上查找
+        assert_eq!(find_repo_config_in(&home.join("project"), None, uid), Some(root.join(REPO_CONFIG_NAME)));
+        // 仓库根目录和家目录本身的配置有效
+        fs::write(repo.join(REPO_CONFIG_NAME), "").unwrap();
+        assert_eq!(find_repo_config_in(&repo.join("src"), None, uid), Some(repo.join(REPO_CONFIG_NAME)));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_trusted_owner() {
+        assert!(trusted_owner(1000, 1000));
+        assert!(trusted_owner(0, 1000));
+        assert!(!trusted_owner(1001, 1000));
+    }
+}
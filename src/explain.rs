@@ -0,0 +1,160 @@
+//! `cursor-guard explain <命令> [参数...]`：说明一次调用会被如何处理，不执行任何命令
+//!
+//! 检测、标准输入/输出的判断都基于 explain 自身所在的环境，
+//! 与 Agent 在同一个 shell 中直接运行该命令时的结果一致。
+
+use cursor_common as common;
+use common::agent;
+use common::pipeline::Position;
+use common::policy::{self, Action, CommandPolicy, Plan};
+use common::procfs::ProcFs;
+
+use std::env;
+use std::process;
+
+const RULE: &str = "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━";
+
+/// explain 入口：args[0] 是被包装的命令名
+pub fn run(args: Vec<String>, find_policy: fn(&str) -> Option<Box<dyn CommandPolicy>>) -> i32 {
+    let Some((name, rest)) = args.split_first() else {
+        eprintln!("用法: cursor-guard explain <命令> [参数...]");
+        return 2;
+    };
+    let Some(policy) = find_policy(name) else {
+        eprintln!("❌ explain: 未知的包装命令 '{}'", name);
+        return 127;
+    };
+
+    println!("🔍 cursor-guard explain: {} {}", name, rest.join(" "));
+    println!("{}", RULE);
+    print_detection();
+
+    let plan = match policy::plan(policy.as_ref(), rest.to_vec()) {
+        Ok(plan) => plan,
+        Err(e) => {
+            println!("📍 原始命令: ❌ {}", e);
+            println!("{}", RULE);
+            return 127;
+        }
+    };
+    print_plan(policy.as_ref(), &plan);
+    println!("{}", RULE);
+    0
+}
+
+fn print_detection() {
+    let config = common::config::global();
+    match &config.agent {
+        Some(found) => println!("🤖 Agent 检测: {}（{}）", found.agent, found.signal),
+        None => println!("🤖 Agent 检测: 未检测到，命令将按原样执行"),
+    }
+    let checks = agent::checks(&config.agents, |name| env::var(name).ok(), &ProcFs::system(), process::id());
+    for (name, signal, matched) in checks {
+        println!("   {} {:<12} {}", if matched { "✓" } else { "✗" }, name, signal);
+    }
+    let tree = &config.process_tree;
+    if tree.enabled {
+        println!("   进程树检测: 已启用（{} 层，模式 {:?}）", tree.max_depth, tree.patterns);
+    } else {
+        println!("   进程树检测: 未启用");
+    }
+}
+
+fn print_plan(policy: &dyn CommandPolicy, plan: &Plan) {
+    let config = common::config::global();
+    println!(
+        "🎛️  强制模式: {}（{}: {}，全局: {}）",
+        plan.mode,
+        policy.name(),
+        policy.mode(),
+        config.mode
+    );
+    println!("📍 原始命令: {}", plan.original.display());
+
+    println!("🧩 参数解析:");
+    for (label, value) in policy.describe_args(&plan.typed) {
+        println!("   {}: {}", label, value);
+    }
+    if !matches!(plan.action, Action::Passthrough(_)) {
+        let stdin = match (plan.reads_stdin, plan.stdin_is_pipe) {
+            (true, true) => "读取，来自管道",
+            (true, false) => "读取，不是管道（不保存）",
+            (false, _) => "不读取",
+        };
+        println!("📥 标准输入: {}", stdin);
+    }
+    let stdout = match plan.position {
+        None => format!("未检查（downstream = {}）", plan.downstream),
+        Some(Position::End) => "管道末端".to_string(),
+        Some(Position::Downstream) => format!("接到下游程序（downstream = {}）", plan.downstream),
+    };
+    println!("📤 标准输出: {}", stdout);
+
+    println!("📏 规则匹配:");
+    if plan.rewrite.enforcements.is_empty() {
+        println!("   （无）");
+    }
+    for enforcement in &plan.rewrite.enforcements {
+        let status = if plan.rules_apply { "" } else { "（不生效）" };
+        println!("   {}: {} → {}{}", enforcement.rule, enforcement.original, enforcement.enforced, status);
+    }
+
+    let settings = &config.repeat;
+    match &plan.repeats {
+        Some(repeats) => println!(
+            "🔁 重复执行: {} 秒内已运行 {} 次（阈值 {}）",
+            settings.window_secs,
+            repeats.earlier.len(),
+            settings.threshold
+        ),
+        None if plan.reads_stdin && plan.stdin_is_pipe && plan.rules_apply => println!("🔁 重复执行: 读取管道输入后判断"),
+        None => {}
+    }
+
+    let action = match plan.action {
+        Action::Passthrough(reason) => format!("直接执行原始命令（{}）", reason),
+        Action::Refuse => format!("拒绝执行（strict 模式，退出码 {}）", common::mode::STRICT_EXIT_CODE),
+        Action::RefuseRepeat => format!("拒绝执行（重复命令，strict 模式，退出码 {}）", common::mode::STRICT_EXIT_CODE),
+        Action::Follow { from_stdin: true } => "跟随管道输入（tail -f 预算）".to_string(),
+        Action::Follow { from_stdin: false } => "跟随文件（tail -f 预算，之后后台记录）".to_string(),
+        Action::Capture => "保存管道输入并执行".to_string(),
+        Action::Run if plan.cache_key.is_some() => "执行原始命令，并保存结果缓存".to_string(),
+        Action::Run => "执行原始命令".to_string(),
+        Action::Replay => "文件没有变化，直接输出缓存的结果".to_string(),
+    };
+    println!("➡️  执行方式: {}", action);
+    if !matches!(plan.action, Action::Refuse | Action::RefuseRepeat) {
+        println!("🚀 最终命令: {} {}", plan.original.display(), quote_args(&plan.args));
+    }
+}
+
+/// 按 shell 写法展示参数，包含特殊字符的参数加单引号
+pub(crate) fn quote_args(args: &[String]) -> String {
+    let quoted: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            let plain = !arg.is_empty()
+                && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_=.,/:+@%".contains(c));
+            if plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect();
+    quoted.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_args() {
+        let args: Vec<String> = ["-C", "20", "a b", "it's", "--exclude-dir={.git,venv}"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(quote_args(&args), "-C 20 'a b' 'it'\\''s' '--exclude-dir={.git,venv}'");
+    }
+}
@@ -0,0 +1,511 @@
+//! `tail -f` / `tail -F` 跟随模式
+//!
+//! Agent 执行命令时必须等命令结束才能拿到输出，而 `tail -f` 永远不会结束：
+//! - 管道输入：GNU tail 对管道忽略 -f，要等上游结束才输出，`server | tail -f` 一直没有输出
+//! - 文件参数：tail 一直跟随文件，Agent 卡住
+//!
+//! 跟随模式边输出边保存，到达时间或行数预算后打印摘要并正常退出；tail 自己退出时返回它的退出码。
+//! 管道输入不经过原始 tail，按 -n/-c 的范围输出（见 [`Window`]），完整输入保存在保存文件中。
+//! 保存受 `[capture] max_total_bytes` 限制，超过后只转发不保存。
+//! 文件参数的后续内容交给后台记录进程继续写入同一个保存文件。记录期间保存文件被加锁，
+//! 清理保存目录时跳过；记录结束后由记录进程写入元数据，大小和 SHA-256 与最终内容一致。
+
+use crate::capture::{self, CaptureMeta, QuotaWriter};
+use crate::config::FollowConfig;
+use crate::{config, guard, lock_exclusive};
+
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 后台记录进程的内部命令名（由多合一二进制分发）
+pub const RECORD_APPLET: &str = "__follow-record";
+
+/// 读取线程每次读取的字节数
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// 跟随结束的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// 输入结束（上游退出或 tail 自己退出）
+    Eof,
+    /// 到达时间预算
+    Time,
+    /// 到达行数预算
+    Lines,
+}
+
+/// 跟随过程的统计
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FollowSummary {
+    pub lines: u64,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    pub reason: StopReason,
+    /// 退出码：原始 tail 自己退出时为它的退出码，到达预算被结束时为 0
+    pub exit_code: i32,
+    /// 超过保存配额，之后的内容只转发没有保存
+    pub quota_exceeded: bool,
+}
+
+/// 跟随的预算
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    /// 最长跟随时间，None 表示不限
+    pub max_time: Option<Duration>,
+    /// 最多输出行数，None 表示不限
+    pub max_lines: Option<u64>,
+}
+
+impl From<&FollowConfig> for Budget {
+    fn from(config: &FollowConfig) -> Self {
+        Budget {
+            max_time: (config.max_secs > 0).then(|| Duration::from_secs(config.max_secs)),
+            max_lines: (config.max_lines > 0).then_some(config.max_lines),
+        }
+    }
+}
+
+/// 跟随时输出的范围，对应 tail 的 -n/-c
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    /// 全部转发（原始 tail 已经按计数输出）
+    All,
+    /// 最后 N 行（`-n N`，默认 10）：结束时输出，与 tail 读到管道结尾时一致
+    LastLines(u64),
+    /// 最后 N 字节（`-c N`）：结束时输出
+    LastBytes(u64),
+    /// 从第 N 行开始（`-n +N`），边收边输出
+    FromLine(u64),
+    /// 从第 N 个字节开始（`-c +N`），边收边输出
+    FromByte(u64),
+}
+
+/// 按 Window 输出：Last* 只保留最后的部分，结束时输出
+struct Windowed<'a, O: Write> {
+    out: &'a mut O,
+    window: Window,
+    /// From*：已经跳过的行数或字节数
+    skipped: u64,
+    /// Last*：最后的部分
+    kept: VecDeque<u8>,
+}
+
+impl<'a, O: Write> Windowed<'a, O> {
+    fn new(out: &'a mut O, window: Window) -> Self {
+        Windowed { out, window, skipped: 0, kept: VecDeque::new() }
+    }
+
+    /// 下游关闭（例如 Agent 已不再读取）时不再输出，但继续保存
+    fn write(&mut self, data: &[u8]) {
+        let data = match self.window {
+            Window::All => data,
+            Window::FromByte(n) => {
+                let skip = (n.saturating_sub(1) - self.skipped).min(data.len() as u64);
+                self.skipped += skip;
+                &data[skip as usize..]
+            }
+            Window::FromLine(n) => {
+                let mut start = 0;
+                while self.skipped < n.saturating_sub(1) && start < data.len() {
+                    if data[start] == b'\n' {
+                        self.skipped += 1;
+                    }
+                    start += 1;
+                }
+                &data[start..]
+            }
+            Window::LastBytes(n) => {
+                self.kept.extend(data);
+                let excess = self.kept.len().saturating_sub(n as usize);
+                self.kept.drain(..excess);
+                return;
+            }
+            Window::LastLines(n) => {
+                self.kept.extend(data);
+                let start = last_lines_start(&self.kept, n);
+                self.kept.drain(..start);
+                return;
+            }
+        };
+        let _ = self.out.write_all(data).and_then(|_| self.out.flush());
+    }
+
+    /// 结束时输出保留的最后部分
+    fn finish(self) {
+        let (front, back) = self.kept.as_slices();
+        let _ = self.out.write_all(front).and_then(|_| self.out.write_all(back)).and_then(|_| self.out.flush());
+    }
+}
+
+/// 最后 n 行的起始位置；末尾没有换行的部分也算一行
+fn last_lines_start(data: &VecDeque<u8>, n: u64) -> usize {
+    if n == 0 {
+        return data.len();
+    }
+    let mut end = data.len();
+    if data.back() == Some(&b'\n') {
+        end -= 1;
+    }
+    let mut seen = 0;
+    for i in (0..end).rev() {
+        if data[i] == b'\n' {
+            seen += 1;
+            if seen == n {
+                return i + 1;
+            }
+        }
+    }
+    0
+}
+
+/// 跟随管道输入：按 window 把标准输入输出到标准输出并保存，不经过原始 tail
+pub fn follow_stdin(capture: &mut impl Write, budget: Budget, window: Window) -> io::Result<FollowSummary> {
+    let chunks = spawn_reader(io::stdin());
+    let stdout = io::stdout();
+    follow_chunks(&chunks, &mut stdout.lock(), capture, budget, window)
+}
+
+/// 跟随文件：运行原始 tail（带 -f），转发并保存它的输出
+/// 返回时原始 tail 已退出或被结束；tail 自己退出（例如文件不存在）时返回它的退出码
+pub fn follow_command<P: AsRef<OsStr>>(
+    original_cmd: P,
+    name: &str,
+    args: &[String],
+    capture: &mut impl Write,
+    budget: Budget,
+) -> io::Result<FollowSummary> {
+    let mut cmd = Command::new(&original_cmd);
+    guard::mark_child(&mut cmd, name);
+    cmd.args(args).stdout(Stdio::piped()).stderr(Stdio::inherit());
+
+    let mut child = cmd.spawn()?;
+    let output = child.stdout.take().expect("stdout 已设置为 piped");
+    let chunks = spawn_reader(output);
+
+    let stdout = io::stdout();
+    let summary = follow_chunks(&chunks, &mut stdout.lock(), capture, budget, Window::All);
+    if let Ok(FollowSummary { reason: StopReason::Eof, .. }) = summary {
+        let status = child.wait()?;
+        return summary.map(|summary| FollowSummary { exit_code: status.code().unwrap_or(1), ..summary });
+    }
+    stop_child(&mut child);
+    summary
+}
+
+/// 启动后台记录进程：在 record_secs 秒内继续把文件的新内容追加到 capture_path
+pub fn spawn_recorder<P: AsRef<OsStr>>(
+    original_cmd: P,
+    name: &str,
+    files: &[String],
+    meta: &CaptureMeta,
+    record_secs: u64,
+) -> io::Result<()> {
+    let exe = std::env::current_exe()?;
+    let mut cmd = Command::new(exe);
+    cmd.arg(RECORD_APPLET)
+        .arg(record_secs.to_string())
+        .arg(name)
+        .arg(serde_json::to_string(meta).map_err(io::Error::other)?)
+        .arg(&original_cmd)
+        .args(["-n", "0", "-F", "--"])
+        .args(files)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // 独立进程组：Agent 结束当前命令时不会顺带杀掉记录进程
+        .process_group(0);
+    cmd.spawn()?;
+    Ok(())
+}
+
+/// 后台记录进程入口：`__follow-record <秒数> <命令名> <元数据 JSON> <原始命令> [参数...]`
+///
+/// 命令名是包装命令的策略名，写入重入标记；元数据中的 path 是保存文件，记录结束后按最终内容写入元数据
+pub fn run_recorder(args: &[String]) -> i32 {
+    let (Some(secs), Some(name), Some(meta), Some(original)) = (args.first(), args.get(1), args.get(2), args.get(3)) else {
+        eprintln!("用法: {} <秒数> <命令名> <元数据 JSON> <原始命令> [参数...]", RECORD_APPLET);
+        return 2;
+    };
+    let (Ok(secs), Ok(meta)) = (secs.parse::<u64>(), serde_json::from_str::<CaptureMeta>(meta)) else {
+        return 2;
+    };
+    let (Ok(file), Ok(lock)) = (OpenOptions::new().append(true).open(&meta.path), File::open(&meta.path)) else {
+        return 1;
+    };
+    let existing = file.metadata().map(|stat| stat.len()).unwrap_or(0);
+    let mut capture = QuotaWriter::appending(file, config::global().capture.max_total_bytes, existing);
+
+    let mut cmd = Command::new(original);
+    guard::mark_child(&mut cmd, name);
+    cmd.args(&args[4..]).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::null());
+    let Ok(mut child) = cmd.spawn() else {
+        return 1;
+    };
+    let Some(output) = child.stdout.take() else {
+        stop_child(&mut child);
+        return 1;
+    };
+    // 先启动 tail 再加锁：跟随阶段的包装器退出前一直持有锁，等锁期间 tail 的输出留在管道中
+    let _ = lock_exclusive(&lock);
+
+    let chunks = spawn_reader(output);
+    let budget = Budget { max_time: Some(Duration::from_secs(secs)), max_lines: None };
+    let _ = follow_chunks(&chunks, &mut io::sink(), &mut capture, budget, Window::All);
+    stop_child(&mut child);
+    match capture::finish(meta) {
+        Ok(_) => 0,
+        Err(_) => 1,
+    }
+}
+
+/// 在后台线程中按块读取 reader，通过通道发送；读到 EOF 或出错时通道关闭
+fn spawn_reader<R: Read + Send + 'static>(mut reader: R) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    if sender.send(buffer[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+    });
+    receiver
+}
+
+/// 转发并保存数据块，直到输入结束或到达预算；行数预算按收到的行计算
+///
+/// 保存超过配额后继续转发，不再保存
+fn follow_chunks<O: Write, C: Write>(
+    chunks: &Receiver<Vec<u8>>,
+    out: &mut O,
+    capture: &mut C,
+    budget: Budget,
+    window: Window,
+) -> io::Result<FollowSummary> {
+    let start = Instant::now();
+    let mut lines = 0u64;
+    let mut bytes = 0u64;
+    let mut quota_exceeded = false;
+    let mut out = Windowed::new(out, window);
+
+    let reason = loop {
+        let chunk = match budget.max_time {
+            Some(max_time) => {
+                let Some(remaining) = max_time.checked_sub(start.elapsed()) else {
+                    break StopReason::Time;
+                };
+                match chunks.recv_timeout(remaining) {
+                    Ok(chunk) => chunk,
+                    Err(RecvTimeoutError::Timeout) => break StopReason::Time,
+                    Err(RecvTimeoutError::Disconnected) => break StopReason::Eof,
+                }
+            }
+            None => match chunks.recv() {
+                Ok(chunk) => chunk,
+                Err(_) => break StopReason::Eof,
+            },
+        };
+
+        // 行数预算：只处理到第 max_lines 行为止
+        let (data, hit_limit) = match budget.max_lines {
+            Some(max_lines) => cut_at_line(&chunk, max_lines - lines),
+            None => (&chunk[..], false),
+        };
+        lines += data.iter().filter(|&&b| b == b'\n').count() as u64;
+        bytes += data.len() as u64;
+
+        if !quota_exceeded {
+            match capture.write_all(data) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::QuotaExceeded => quota_exceeded = true,
+                Err(e) => return Err(e),
+            }
+        }
+        out.write(data);
+
+        if hit_limit {
+            break StopReason::Lines;
+        }
+    };
+
+    out.finish();
+    capture.flush()?;
+    Ok(FollowSummary { lines, bytes, elapsed: start.elapsed(), reason, exit_code: 0, quota_exceeded })
+}
+
+/// 截取 chunk 中前 remaining 行；返回截取的数据以及是否到达行数上限
+fn cut_at_line(chunk: &[u8], remaining: u64) -> (&[u8], bool) {
+    let mut seen = 0u64;
+    for (i, &b) in chunk.iter().enumerate() {
+        if b == b'\n' {
+            seen += 1;
+            if seen == remaining {
+                return (&chunk[..=i], true);
+            }
+        }
+    }
+    (chunk, remaining == 0)
+}
+
+fn stop_child(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// 打印跟随结束摘要（统一格式）
+pub fn print_follow_summary(summary: &FollowSummary, capture_path: &str, recording: Option<u64>) {
+    let why = match summary.reason {
+        StopReason::Eof if summary.exit_code != 0 => format!("已退出（退出码 {}）", summary.exit_code),
+        StopReason::Eof => "输入已结束".to_string(),
+        StopReason::Time => format!("已到达 {} 秒时间预算", summary.elapsed.as_secs()),
+        StopReason::Lines => format!("已到达 {} 行预算", summary.lines),
+    };
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    eprintln!("⏱️  [Cursor 强制最佳实践] tail -f {}，已自动结束以免阻塞", why);
+    eprintln!(
+        "📊 共跟随 {:.1} 秒，收到 {} 行（{} 字节）",
+        summary.elapsed.as_secs_f64(),
+        summary.lines,
+        summary.bytes
+    );
+    eprintln!("📁 完整内容保存在: {}", capture_path);
+    match recording {
+        Some(secs) => eprintln!("📼 后续内容在后台继续追加到同一文件（最多 {} 秒）", secs),
+        None if summary.reason != StopReason::Eof => eprintln!("📼 管道输入无法在后台继续记录，上游将在下次写入时结束"),
+        None => {}
+    }
+    eprintln!("💡 需要更多内容时直接读取此文件，不要重新运行 tail -f");
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+}
+
+/// warn 模式下跟随前的提示：不改写参数，但仍按预算结束，避免会话一直挂起
+pub fn print_budget_warning(budget: &Budget) {
+    let mut limits = Vec::new();
+    if let Some(max_time) = budget.max_time {
+        limits.push(format!("最长 {} 秒", max_time.as_secs()));
+    }
+    if let Some(max_lines) = budget.max_lines {
+        limits.push(format!("最多 {} 行", max_lines));
+    }
+    let limits = if limits.is_empty() { "不限".to_string() } else { limits.join("、") };
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    eprintln!("⚠️  [Cursor 最佳实践建议] tail -f 不会自己结束，warn 模式下仍按跟随预算（{}）运行", limits);
+    eprintln!("💡 需要一直跟随时请在自己的终端中运行，或加 --cg-raw 绕过");
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel_of(chunks: &[&[u8]]) -> Receiver<Vec<u8>> {
+        let (sender, receiver) = mpsc::channel();
+        for chunk in chunks {
+            sender.send(chunk.to_vec()).unwrap();
+        }
+        receiver
+    }
+
+    #[test]
+    fn test_follow_until_eof() {
+        let chunks = channel_of(&[b"a\nb\n", b"c\n"]);
+        let (mut out, mut capture) = (Vec::new(), Vec::new());
+        let budget = Budget { max_time: None, max_lines: None };
+        let summary = follow_chunks(&chunks, &mut out, &mut capture, budget, Window::All).unwrap();
+        assert_eq!(summary.reason, StopReason::Eof);
+        assert_eq!(summary.lines, 3);
+        assert_eq!(out, b"a\nb\nc\n");
+        assert_eq!(capture, out);
+    }
+
+    #[test]
+    fn test_follow_stops_at_line_budget() {
+        let chunks = channel_of(&[b"1\n2\n", b"3\n4\n5\n"]);
+        let (mut out, mut capture) = (Vec::new(), Vec::new());
+        let budget = Budget { max_time: None, max_lines: Some(3) };
+        let summary = follow_chunks(&chunks, &mut out, &mut capture, budget, Window::All).unwrap();
+        assert_eq!(summary.reason, StopReason::Lines);
+        assert_eq!(summary.lines, 3);
+        assert_eq!(out, b"1\n2\n3\n");
+    }
+
+    #[test]
+    fn test_follow_stops_at_time_budget() {
+        // 发送端一直不关闭，模拟永不结束的上游
+        let (sender, receiver) = mpsc::channel();
+        sender.send(b"first\n".to_vec()).unwrap();
+        let (mut out, mut capture) = (Vec::new(), Vec::new());
+        let budget = Budget { max_time: Some(Duration::from_millis(100)), max_lines: None };
+        let summary = follow_chunks(&receiver, &mut out, &mut capture, budget, Window::All).unwrap();
+        assert_eq!(summary.reason, StopReason::Time);
+        assert_eq!(capture, b"first\n");
+        drop(sender);
+    }
+
+    #[test]
+    fn test_follow_window() {
+        let window = |window, chunks: &[&[u8]]| {
+            let (mut out, mut capture) = (Vec::new(), Vec::new());
+            let budget = Budget { max_time: None, max_lines: None };
+            follow_chunks(&channel_of(chunks), &mut out, &mut capture, budget, window).unwrap();
+            assert_eq!(capture, chunks.concat());
+            String::from_utf8(out).unwrap()
+        };
+        // producer | tail -f -n 2：输出最后 2 行，完整输入仍然保存
+        assert_eq!(window(Window::LastLines(2), &[b"1\n2\n", b"3\n4"]), "3\n4");
+        assert_eq!(window(Window::LastLines(2), &[b"1\n2\n3", b"\n"]), "2\n3\n");
+        assert_eq!(window(Window::LastLines(0), &[b"1\n"]), "");
+        assert_eq!(window(Window::LastBytes(3), &[b"abcd", b"ef"]), "def");
+        assert_eq!(window(Window::FromLine(3), &[b"1\n2", b"\n3\n4\n"]), "3\n4\n");
+        assert_eq!(window(Window::FromLine(0), &[b"1\n2\n"]), "1\n2\n");
+        assert_eq!(window(Window::FromByte(3), &[b"a", b"bcd"]), "cd");
+    }
+
+    #[test]
+    fn test_follow_stops_saving_at_quota() {
+        let chunks = channel_of(&[b"ab\n", b"cd\n"]);
+        let (mut out, mut capture) = (Vec::new(), QuotaWriter::new(Vec::new(), 4));
+        let budget = Budget { max_time: None, max_lines: None };
+        let summary = follow_chunks(&chunks, &mut out, &mut capture, budget, Window::All).unwrap();
+        assert!(summary.quota_exceeded);
+        assert_eq!(out, b"ab\ncd\n");
+    }
+
+    #[test]
+    fn test_follow_command_reports_exit_code() {
+        let Some(tail) = ["/usr/bin/tail", "/bin/tail"].into_iter().find(|path| std::path::Path::new(path).exists()) else {
+            return;
+        };
+        let dir = std::env::temp_dir().join(format!("cursor_test_follow_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut capture = File::create(dir.join("capture.txt")).unwrap();
+        let budget = Budget { max_time: Some(Duration::from_secs(10)), max_lines: None };
+        let args = vec!["-f".to_string(), dir.join("missing.log").display().to_string()];
+        let summary = follow_command(tail, "tail", &args, &mut capture, budget).unwrap();
+        assert_eq!(summary.reason, StopReason::Eof);
+        assert_ne!(summary.exit_code, 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_budget_from_config() {
+        let config = FollowConfig { max_secs: 0, max_lines: 10, record_secs: 60 };
+        let budget = Budget::from(&config);
+        assert_eq!(budget.max_time, None);
+        assert_eq!(budget.max_lines, Some(10));
+    }
+}
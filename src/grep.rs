@@ -1,99 +1,403 @@
 use cursor_common as common;
+use common::config::Downstream;
+use common::mode::Mode;
+use common::input::{scan_args, Arg, Operands};
+use common::policy::{describe_files, CommandPolicy, Enforcement, Rewrite};
 
-use std::env;
 use std::process;
-use std::fs;
-use std::io::{self, BufRead};
 
-const ORIGINAL_GREP: &str = "/usr/bin/grep";
 const GREP_EXTRA_ARGS: &[&str] = &["--color=auto", "--exclude-dir={.bzr,CVS,.git,.hg,.svn,.idea,.tox,.venv,venv}"];
 
-fn main() {
-    let args: Vec<String> = env::args().skip(1).collect();
-    
-    // 如果不在 Cursor 环境中，直接调用原始命令
-    if !common::is_in_cursor() {
-        let exit_code = common::execute_original_command(ORIGINAL_GREP, &args)
-            .unwrap_or(1);
-        process::exit(exit_code);
-    }
-    
-    // 在 Cursor 环境中，应用强制最佳实践
-    enforce_grep_best_practices(args);
+/// 包装器入口（由 main.rs 按 argv[0] 分发）
+pub fn run(args: Vec<String>) {
+    process::exit(common::policy::run_policy(policy().as_ref(), args));
 }
 
-fn enforce_grep_best_practices(mut args: Vec<String>) {
-    // 检查是否有管道输入
-    let has_pipe_input = !atty::is(atty::Stream::Stdin);
-    
-    if has_pipe_input {
-        // 保存管道输入到临时文件（使用统一接口）
-        match common::save_stdin_to_file("grep_input") {
-            Ok(Some(tmp_file)) => {
-                // 检查是否已有上下文参数
-                let has_context = has_context_arg(&args);
-                
-                if !has_context {
-                    common::print_enforcement_message("grep", "grep -C 20");
-                    args.insert(0, "-C".to_string());
-                    args.insert(1, "20".to_string());
-                }
-                
-                // 添加额外参数
-                let mut final_args = Vec::new();
-                for arg in GREP_EXTRA_ARGS {
-                    final_args.push(arg.to_string());
-                }
-                final_args.extend(args);
-                final_args.push(tmp_file);
-                
-                let exit_code = common::execute_original_command(ORIGINAL_GREP, &final_args)
-                    .unwrap_or(1);
-                process::exit(exit_code);
-            }
-            Ok(None) => {
-                // 没有输入，直接执行
-                let exit_code = common::execute_original_command(ORIGINAL_GREP, &args)
-                    .unwrap_or(1);
-                process::exit(exit_code);
-            }
-            Err(e) => {
-                eprintln!("❌ 无法保存管道输入: {}", e);
-                process::exit(1);
-            }
-        }
-    } else {
-        // 没有管道输入，检查是否有上下文参数
-        let has_context = has_context_arg(&args);
-        
-        if !has_context {
-            common::print_enforcement_message("grep", "grep -C 20");
+/// 按当前配置构造 grep 策略
+pub fn policy() -> Box<dyn CommandPolicy> {
+    let config = &common::config::global().grep;
+    Box::new(GrepPolicy {
+        context: config.context,
+        path: config.path.clone(),
+        downstream: config.downstream,
+        mode: config.mode,
+    })
+}
+
+/// grep 强制规则：未指定上下文时自动添加 -C <context>（默认 20）
+struct GrepPolicy {
+    context: u32,
+    /// 配置中指定的原始 grep 路径
+    path: String,
+    /// 输出接到下游程序时的处理方式
+    downstream: Downstream,
+    /// 强制模式
+    mode: Mode,
+}
+
+impl CommandPolicy for GrepPolicy {
+    fn name(&self) -> &str {
+        "grep"
+    }
+
+    fn original_names(&self) -> Vec<&str> {
+        // Homebrew 安装的 GNU grep 名为 ggrep
+        vec!["grep", "ggrep"]
+    }
+
+    fn original_override(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+
+    fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn downstream(&self) -> Downstream {
+        self.downstream
+    }
+
+    fn operands(&self, args: &[String]) -> Operands {
+        grep_operands(args)
+    }
+
+    fn describe_args(&self, args: &[String]) -> Vec<(String, String)> {
+        let scan = scan_args(args, grep_takes_value);
+        let mode = OutputMode::detect(&scan);
+        let context = if has_context_arg(&scan) { "已指定" } else { "未指定" };
+        vec![
+            ("输出模式".to_string(), format!("{}{}", mode.label(), if mode.uses_context() { "" } else { "（不需要上下文）" })),
+            ("上下文".to_string(), context.to_string()),
+            ("文件参数".to_string(), describe_files(args, &grep_operands(args))),
+        ]
+    }
+
+    fn colors_terminal(&self, args: &[String]) -> bool {
+        // 以最后一个 --color 为准；auto（包装器默认添加）或未指定时取决于是否输出到终端
+        let color = scan_args(args, grep_takes_value)
+            .into_iter()
+            .rev()
+            .find_map(|arg| match arg {
+                Arg::Opt { name, value, .. } if name == "--color" || name == "--colour" => Some(value),
+                _ => None,
+            })
+            .flatten();
+        !matches!(color.as_deref(), Some("always" | "yes" | "force" | "never" | "no" | "none"))
+    }
+
+    fn rewrite_args(&self, mut args: Vec<String>) -> Rewrite {
+        let mut enforcements = Vec::new();
+
+        // 只有逐行输出匹配内容的模式才需要上下文，且用户未自行指定时才添加
+        let scan = scan_args(&args, grep_takes_value);
+        if OutputMode::detect(&scan).uses_context() && !has_context_arg(&scan) {
+            enforcements.push(Enforcement::new("grep.context", "grep", format!("grep -C {}", self.context)));
             args.insert(0, "-C".to_string());
-            args.insert(1, "20".to_string());
+            args.insert(1, self.context.to_string());
         }
-        
+
         // 添加额外参数
-        let mut final_args = Vec::new();
-        for arg in GREP_EXTRA_ARGS {
-            final_args.push(arg.to_string());
-        }
+        let mut final_args: Vec<String> = GREP_EXTRA_ARGS.iter().map(|arg| arg.to_string()).collect();
         final_args.extend(args);
-        
-        let exit_code = common::execute_original_command(ORIGINAL_GREP, &final_args)
-            .unwrap_or(1);
-        process::exit(exit_code);
+
+        Rewrite { args: final_args, enforcements }
     }
 }
 
-fn has_context_arg(args: &[String]) -> bool {
-    for arg in args {
-        if arg.starts_with("-A") || arg.starts_with("-B") || arg.starts_with("-C") ||
-           arg.starts_with("--context") || arg.starts_with("--after-context") || 
-           arg.starts_with("--before-context") {
-            return true;
+/// 需要值的 grep 选项
+fn grep_takes_value(name: &str) -> bool {
+    matches!(
+        name,
+        "-A" | "-B" | "-C" | "-D" | "-d" | "-e" | "-f" | "-m"
+            | "--after-context" | "--before-context" | "--context"
+            | "--devices" | "--directories" | "--regexp" | "--file"
+            | "--max-count" | "--binary-files" | "--include" | "--exclude"
+            | "--exclude-from" | "--include-from" | "--exclude-dir" | "--label" | "--group-separator"
+    )
+}
+
+/// 解析 grep 的文件参数：没有 -e/-f 时第一个操作数是模式；
+/// 递归搜索且没有文件参数时 grep 搜索当前目录，不读标准输入。
+/// -f 的模式文件和 --exclude-from/--include-from 的列表文件记为选项读取的文件
+fn grep_operands(args: &[String]) -> Operands {
+    let scan = scan_args(args, grep_takes_value);
+    let mut pattern_given = false;
+    let mut recursive = false;
+    let mut option_files = Vec::new();
+    for arg in &scan {
+        if let Arg::Opt { name, value, .. } = arg {
+            if matches!(name.as_str(), "-f" | "--file" | "--exclude-from" | "--include-from") {
+                option_files.extend(value.clone());
+            }
+            match name.as_str() {
+                "-e" | "--regexp" | "-f" | "--file" => pattern_given = true,
+                "-r" | "-R" | "--recursive" | "--dereference-recursive" => recursive = true,
+                "-d" | "--directories" => recursive = value.as_deref() == Some("recurse"),
+                _ => {}
+            }
         }
     }
-    false
+
+    let mut operands = Operands::from_scan(&scan);
+    if !pattern_given && !operands.files.is_empty() {
+        operands.files.remove(0);
+    }
+    operands.stdin_by_default = !recursive;
+    operands.option_files = option_files;
+    operands
+}
+
+/// grep 的输出模式，按优先级排序：同时出现时 -q > -l/-L > -c > -o
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum OutputMode {
+    /// 默认：输出匹配行
+    Lines,
+    /// -o：只输出匹配部分，上下文只会多出 `--` 分隔符
+    OnlyMatching,
+    /// -c：只输出匹配行数，GNU grep 忽略上下文
+    Count,
+    /// -l / -L：只输出文件名
+    FileNames,
+    /// -q：不输出，只看退出码
+    Quiet,
+}
+
+impl OutputMode {
+    /// 从扫描结果中判断输出模式；多个模式同时出现时取 grep 实际生效的那个
+    fn detect(scan: &[Arg]) -> OutputMode {
+        let mut mode = OutputMode::Lines;
+        for arg in scan {
+            let Arg::Opt { name, .. } = arg else { continue };
+            let found = match name.as_str() {
+                "-o" | "--only-matching" => OutputMode::OnlyMatching,
+                "-c" | "--count" => OutputMode::Count,
+                "-l" | "--files-with-matches" | "-L" | "--files-without-match" => OutputMode::FileNames,
+                "-q" | "--quiet" | "--silent" => OutputMode::Quiet,
+                _ => continue,
+            };
+            mode = mode.max(found);
+        }
+        mode
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            OutputMode::Lines => "匹配行",
+            OutputMode::OnlyMatching => "只输出匹配部分（-o）",
+            OutputMode::Count => "计数（-c）",
+            OutputMode::FileNames => "文件名（-l/-L）",
+            OutputMode::Quiet => "静默（-q）",
+        }
+    }
+
+    /// 该模式下 -C 是否有意义
+    fn uses_context(self) -> bool {
+        self == OutputMode::Lines
+    }
+}
+
+/// 是否已指定上下文：-A/-B/-C、对应长选项或旧式 -NUM
+fn has_context_arg(scan: &[Arg]) -> bool {
+    scan.iter().any(|arg| match arg {
+        Arg::Opt { name, .. } => {
+            matches!(
+                name.as_str(),
+                "-A" | "-B" | "-C" | "--context" | "--after-context" | "--before-context"
+            ) || (name.len() > 1 && name[1..].chars().all(|c| c.is_ascii_digit()))
+        }
+        Arg::Operand { .. } => false,
+    })
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: GrepPolicy = GrepPolicy { context: 20, path: String::new(), downstream: Downstream::Inherit, mode: Mode::Inherit };
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_adds_context_when_missing() {
+        let rewrite = POLICY.rewrite_args(args(&["error", "log.txt"]));
+        assert_eq!(&rewrite.args[2..], &args(&["-C", "20", "error", "log.txt"])[..]);
+        assert_eq!(rewrite.enforcements.len(), 1);
+        assert_eq!(rewrite.enforcements[0].rule, "grep.context");
+    }
+
+    #[test]
+    fn test_keeps_existing_context() {
+        let rewrite = POLICY.rewrite_args(args(&["-A", "3", "error"]));
+        assert_eq!(&rewrite.args[2..], &args(&["-A", "3", "error"])[..]);
+        assert!(rewrite.enforcements.is_empty());
+    }
+
+    #[test]
+    fn test_operands_skip_pattern() {
+        let list = args(&["-i", "error", "log.txt"]);
+        let operands = grep_operands(&list);
+        assert_eq!(operands.files, vec![2]);
+        assert!(!operands.reads_stdin(&list));
+    }
+
+    #[test]
+    fn test_operands_pattern_only_reads_stdin() {
+        let list = args(&["-A", "3", "error"]);
+        assert!(grep_operands(&list).reads_stdin(&list));
+    }
+
+    #[test]
+    fn test_operands_with_explicit_pattern() {
+        let list = args(&["-e", "error", "-"]);
+        let operands = grep_operands(&list);
+        assert_eq!(operands.files, vec![2]);
+        assert!(operands.reads_stdin(&list));
+    }
+
+    #[test]
+    fn test_colors_terminal() {
+        let rewritten = POLICY.rewrite_args(args(&["error", "log.txt"])).args;
+        assert!(POLICY.colors_terminal(&rewritten));
+        assert!(POLICY.colors_terminal(&args(&["--colour", "error"])));
+        assert!(!POLICY.colors_terminal(&args(&["--color=auto", "--color=never", "error"])));
+        assert!(!POLICY.colors_terminal(&args(&["--colour=always", "error"])));
+    }
+
+    #[test]
+    fn test_operands_include_option_files() {
+        let list = args(&["-f", "patterns.txt", "--exclude-from=skip.txt", "-r", "src"]);
+        let operands = grep_operands(&list);
+        assert_eq!(operands.files, vec![4]);
+        assert_eq!(operands.option_files, vec!["patterns.txt", "skip.txt"]);
+        assert_eq!(operands.input_files(&list), vec!["src", "patterns.txt", "skip.txt"]);
+    }
+
+    #[test]
+    fn test_editing_pattern_file_misses_cache() {
+        use common::cache::{self, Cache};
+        use common::config::CacheConfig;
+        use common::repeat;
+        use std::fs;
+
+        let dir = std::env::temp_dir().join(format!("cursor_test_grep_cache_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let patterns = dir.join("patterns.txt");
+        let log = dir.join("app.log");
+        fs::write(&patterns, "error\n").unwrap();
+        fs::write(&log, "error 1\nwarn 2\n").unwrap();
+        let list = args(&["-f", patterns.to_str().unwrap(), log.to_str().unwrap()]);
+        let key = || {
+            let operands = grep_operands(&list);
+            let files = operands.input_files(&list);
+            assert!(cache::cacheable_files(&files));
+            repeat::key(&list, "/repo", &repeat::files_input(&files))
+        };
+
+        let config = CacheConfig { enabled: true, max_age_secs: 300, max_bytes: 1024 };
+        let cache = Cache::new(&dir.join("cache"));
+        let before = key();
+        cache.run_and_store(&before, std::path::Path::new("true"), "grep", &[], "grep", &config).unwrap();
+        let now = chrono::Local::now().timestamp();
+        assert!(cache.lookup(&before, now, &config).is_some());
 
+        fs::write(&patterns, "error\nwarn\n").unwrap();
+        let after = key();
+        assert_ne!(before, after);
+        assert!(cache.lookup(&after, now, &config).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_operands_recursive_without_files() {
+        let list = args(&["-rn", "TODO"]);
+        assert!(!grep_operands(&list).reads_stdin(&list));
+        let list = args(&["--directories=recurse", "TODO"]);
+        assert!(!grep_operands(&list).reads_stdin(&list));
+    }
+
+    #[test]
+    fn test_context_is_configurable() {
+        let policy = GrepPolicy { context: 5, ..POLICY };
+        let rewrite = policy.rewrite_args(args(&["error"]));
+        assert_eq!(&rewrite.args[2..], &args(&["-C", "5", "error"])[..]);
+        assert_eq!(rewrite.enforcements[0].enforced, "grep -C 5");
+    }
+
+    fn assert_no_context(list: &[&str]) {
+        let rewrite = POLICY.rewrite_args(args(list));
+        assert_eq!(&rewrite.args[2..], &args(list)[..], "{:?}", list);
+        assert!(rewrite.enforcements.is_empty(), "{:?}", list);
+    }
+
+    #[test]
+    fn test_files_with_matches_skips_context() {
+        assert_no_context(&["-l", "error", "src"]);
+        assert_no_context(&["--files-with-matches", "error", "src"]);
+        assert_no_context(&["-rl", "error"]);
+    }
+
+    #[test]
+    fn test_files_without_match_skips_context() {
+        assert_no_context(&["-L", "error", "src"]);
+        assert_no_context(&["--files-without-match", "error"]);
+    }
+
+    #[test]
+    fn test_count_skips_context() {
+        assert_no_context(&["-c", "error", "log.txt"]);
+        assert_no_context(&["--count", "error"]);
+        assert_no_context(&["-ic", "error"]);
+    }
+
+    #[test]
+    fn test_quiet_skips_context() {
+        assert_no_context(&["-q", "error", "log.txt"]);
+        assert_no_context(&["--quiet", "error"]);
+        assert_no_context(&["--silent", "error"]);
+    }
+
+    #[test]
+    fn test_only_matching_skips_context() {
+        assert_no_context(&["-o", "[0-9]+", "log.txt"]);
+        assert_no_context(&["--only-matching", "-E", "[0-9]+"]);
+        assert_no_context(&["-oE", "[0-9]+"]);
+    }
+
+    #[test]
+    fn test_mode_letters_in_values_are_not_modes() {
+        // -e 的值 "-l" 是模式，不是 -l 选项
+        let rewrite = POLICY.rewrite_args(args(&["-e", "-l", "log.txt"]));
+        assert_eq!(rewrite.enforcements.len(), 1);
+        // -- 之后的 -c 是模式
+        let rewrite = POLICY.rewrite_args(args(&["--", "-c", "log.txt"]));
+        assert_eq!(rewrite.enforcements.len(), 1);
+    }
+
+    #[test]
+    fn test_detect_output_mode_precedence() {
+        let detect = |list: &[&str]| OutputMode::detect(&scan_args(&args(list), grep_takes_value));
+        assert_eq!(detect(&["-n", "x"]), OutputMode::Lines);
+        assert_eq!(detect(&["-o", "-c", "x"]), OutputMode::Count);
+        assert_eq!(detect(&["-c", "-l", "x"]), OutputMode::FileNames);
+        assert_eq!(detect(&["-l", "-q", "x"]), OutputMode::Quiet);
+    }
+
+    #[test]
+    fn test_describe_args() {
+        let described = POLICY.describe_args(&args(&["-c", "error", "log.txt"]));
+        assert_eq!(described[0].1, "计数（-c）（不需要上下文）");
+        assert_eq!(described[1].1, "未指定");
+        assert_eq!(described[2].1, "log.txt");
+    }
+
+    #[test]
+    fn test_existing_context_forms() {
+        assert_no_context(&["-iC5", "error"]);
+        assert_no_context(&["-5", "error"]);
+        assert_no_context(&["--context=3", "error"]);
+        assert_no_context(&["--after-context", "2", "error"]);
+    }
+}
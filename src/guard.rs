@@ -0,0 +1,130 @@
+//! 递归保护
+//!
+//! 如果原始命令的查找结果因为符号链接或错误安装指回了包装器，
+//! `execute_original_command` 会不停地启动自己。这里用两道保险防止这种情况：
+//! 1. 重入标记：启动原始命令时在环境中写入 `CURSOR_GUARD_ACTIVE=<命令名>:<pid>`，
+//!    新进程发现自己的父进程正是以同名命令启动它的包装器，即可判定为递归
+//! 2. 深度限制：每经过一层包装器 `CURSOR_GUARD_DEPTH` 加一，超过上限即报错
+
+use std::env;
+use std::fmt;
+use std::os::unix::process::parent_id;
+use std::process::{self, Command};
+
+/// 重入标记环境变量
+pub const ACTIVE_ENV: &str = "CURSOR_GUARD_ACTIVE";
+
+/// 嵌套深度环境变量
+pub const DEPTH_ENV: &str = "CURSOR_GUARD_DEPTH";
+
+/// 递归保护触发的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuardError {
+    /// 父进程就是启动同名原始命令的包装器
+    Reentered { name: String, parent: u32 },
+    /// 包装器嵌套层数超过上限
+    TooDeep { depth: u32, max_depth: u32 },
+}
+
+impl fmt::Display for GuardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuardError::Reentered { name, parent } => write!(
+                f,
+                "检测到递归调用：包装器（pid {}）启动的原始 {} 指向了包装器自身，请检查 PATH 或配置中的 path",
+                parent, name
+            ),
+            GuardError::TooDeep { depth, max_depth } => write!(
+                f,
+                "包装器嵌套层数 {} 超过上限 {}，可能存在循环调用",
+                depth, max_depth
+            ),
+        }
+    }
+}
+
+/// 检查当前进程是否由包装器递归启动
+pub fn check(name: &str, max_depth: u32) -> Result<(), GuardError> {
+    check_env(
+        name,
+        parent_id(),
+        env::var(ACTIVE_ENV).ok().as_deref(),
+        env::var(DEPTH_ENV).ok().as_deref(),
+        max_depth,
+    )
+}
+
+fn check_env(
+    name: &str,
+    parent: u32,
+    active: Option<&str>,
+    depth: Option<&str>,
+    max_depth: u32,
+) -> Result<(), GuardError> {
+    if let Some((active_name, active_pid)) = active.and_then(|v| v.rsplit_once(':')) {
+        if active_name == name && active_pid.parse() == Ok(parent) {
+            return Err(GuardError::Reentered { name: name.to_string(), parent });
+        }
+    }
+
+    let depth = current_depth(depth);
+    if depth >= max_depth {
+        return Err(GuardError::TooDeep { depth, max_depth });
+    }
+    Ok(())
+}
+
+fn current_depth(depth: Option<&str>) -> u32 {
+    depth.and_then(|d| d.parse().ok()).unwrap_or(0)
+}
+
+/// 为即将启动的原始命令写入重入标记和新的深度
+///
+/// name 是包装命令的策略名（`policy.name()`），与 [`check`] 比较的名称一致；
+/// 原始命令的文件名可能不同（例如 `ggrep`），不能用来做标记
+pub fn mark_child(cmd: &mut Command, name: &str) {
+    let depth = current_depth(env::var(DEPTH_ENV).ok().as_deref());
+    cmd.env(ACTIVE_ENV, format!("{}:{}", name, process::id()))
+        .env(DEPTH_ENV, (depth + 1).to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_markers_passes() {
+        assert_eq!(check_env("grep", 100, None, None, 4), Ok(()));
+    }
+
+    #[test]
+    fn test_reentry_from_same_wrapper_trips() {
+        let result = check_env("grep", 4242, Some("grep:4242"), Some("1"), 4);
+        assert_eq!(result, Err(GuardError::Reentered { name: "grep".to_string(), parent: 4242 }));
+    }
+
+    #[test]
+    fn test_marker_from_other_process_is_ignored() {
+        // 不同父进程：例如原始命令再启动的其他程序又调用了 grep
+        assert_eq!(check_env("grep", 5000, Some("grep:4242"), Some("1"), 4), Ok(()));
+        // 不同命令名：例如 egrep 脚本 exec grep
+        assert_eq!(check_env("grep", 4242, Some("egrep:4242"), Some("1"), 4), Ok(()));
+    }
+
+    #[test]
+    fn test_depth_limit_trips() {
+        let result = check_env("tail", 1, None, Some("4"), 4);
+        assert_eq!(result, Err(GuardError::TooDeep { depth: 4, max_depth: 4 }));
+        assert_eq!(check_env("tail", 1, None, Some("3"), 4), Ok(()));
+    }
+
+    #[test]
+    fn test_mark_child_sets_markers() {
+        let mut cmd = Command::new("true");
+        mark_child(&mut cmd, "grep");
+        let envs: Vec<_> = cmd.get_envs().collect();
+        let active = envs.iter().find(|(k, _)| *k == ACTIVE_ENV).and_then(|(_, v)| *v).unwrap();
+        assert_eq!(active.to_string_lossy(), format!("grep:{}", process::id()));
+        assert!(envs.iter().any(|(k, _)| *k == DEPTH_ENV));
+    }
+}
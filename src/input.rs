@@ -0,0 +1,251 @@
+//! 输入来源判断
+//!
+//! 只有命令确实会读取标准输入（没有文件参数，或显式给出 `-`），且标准输入是管道时，
+//! 才需要保存管道输入。Agent 的 shell 经常以非终端的标准输入运行，
+//! 所以不能只凭 "stdin 不是 tty" 就去读管道。
+
+use std::fs::{self, File};
+use std::io;
+use std::os::fd::AsFd;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+/// 标准输入的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdinKind {
+    /// 交互终端
+    Terminal,
+    /// 管道或套接字：上游命令的输出，需要保存
+    Pipe,
+    /// 重定向自 /dev/null：没有输入
+    DevNull,
+    /// 重定向自普通文件：输入本来就在磁盘上，无需保存
+    File,
+    /// 标准输入已关闭
+    Closed,
+    /// 其他设备
+    Other,
+}
+
+impl StdinKind {
+    /// 是否需要把输入保存下来
+    pub fn is_pipe(self) -> bool {
+        self == StdinKind::Pipe
+    }
+}
+
+/// 检测当前进程标准输入的类型
+pub fn stdin_kind() -> StdinKind {
+    if atty::is(atty::Stream::Stdin) {
+        return StdinKind::Terminal;
+    }
+    let meta = match io::stdin()
+        .as_fd()
+        .try_clone_to_owned()
+        .and_then(|fd| File::from(fd).metadata())
+    {
+        Ok(meta) => meta,
+        Err(_) => return StdinKind::Closed,
+    };
+
+    let file_type = meta.file_type();
+    if file_type.is_fifo() || file_type.is_socket() {
+        StdinKind::Pipe
+    } else if file_type.is_file() {
+        StdinKind::File
+    } else if file_type.is_char_device() && is_dev_null(meta.rdev()) {
+        StdinKind::DevNull
+    } else {
+        StdinKind::Other
+    }
+}
+
+fn is_dev_null(rdev: u64) -> bool {
+    fs::metadata("/dev/null")
+        .map(|meta| meta.rdev() == rdev)
+        .unwrap_or(false)
+}
+
+/// 扫描后的一个命令行参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arg {
+    /// 选项：`name` 形如 `-n`、`--lines`、`-5`，`value` 为选项的值（内联或下一个参数）
+    Opt { index: usize, name: String, value: Option<String> },
+    /// 操作数（文件、模式等），`index` 为在参数列表中的下标
+    Operand { index: usize },
+}
+
+/// 按 getopt 规则扫描参数
+///
+/// `takes_value` 判断选项（`-n` 或 `--lines` 形式）是否需要值。
+/// 支持短选项组合（`-qn5`）、`--name=value`、`--` 结束选项，单独的 `-` 视为操作数。
+pub fn scan_args(args: &[String], takes_value: impl Fn(&str) -> bool) -> Vec<Arg> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    let mut only_operands = false;
+
+    while i < args.len() {
+        let arg = &args[i];
+        let index = i;
+        i += 1;
+
+        if only_operands || arg == "-" || !arg.starts_with('-') {
+            result.push(Arg::Operand { index });
+            continue;
+        }
+        if arg == "--" {
+            only_operands = true;
+            continue;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            match long.split_once('=') {
+                Some((name, value)) => result.push(Arg::Opt {
+                    index,
+                    name: format!("--{}", name),
+                    value: Some(value.to_string()),
+                }),
+                None => {
+                    let value = if takes_value(arg) { next_value(args, &mut i) } else { None };
+                    result.push(Arg::Opt { index, name: arg.clone(), value });
+                }
+            }
+            continue;
+        }
+
+        // 纯数字的旧式写法：-5
+        if arg[1..].chars().all(|c| c.is_ascii_digit()) {
+            result.push(Arg::Opt { index, name: arg.clone(), value: None });
+            continue;
+        }
+
+        // 短选项组合：-qn5、-iA3
+        for (pos, c) in arg.char_indices().skip(1) {
+            let name = format!("-{}", c);
+            if takes_value(&name) {
+                let rest = &arg[pos + c.len_utf8()..];
+                let value = if rest.is_empty() { next_value(args, &mut i) } else { Some(rest.to_string()) };
+                result.push(Arg::Opt { index, name, value });
+                break;
+            }
+            result.push(Arg::Opt { index, name, value: None });
+        }
+    }
+    result
+}
+
+fn next_value(args: &[String], i: &mut usize) -> Option<String> {
+    let value = args.get(*i).cloned();
+    if value.is_some() {
+        *i += 1;
+    }
+    value
+}
+
+/// 命令的文件参数
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Operands {
+    /// 文件参数在参数列表中的下标
+    pub files: Vec<usize>,
+    /// 没有文件参数时是否读取标准输入（例如 `grep -r` 默认搜索当前目录）
+    pub stdin_by_default: bool,
+    /// 通过选项读取的文件（例如 `grep -f` 的模式文件），同样决定命令的结果
+    pub option_files: Vec<String>,
+}
+
+impl Operands {
+    /// 所有操作数都是文件的简单命令
+    pub fn from_scan(scan: &[Arg]) -> Operands {
+        let files = scan
+            .iter()
+            .filter_map(|arg| match arg {
+                Arg::Operand { index } => Some(*index),
+                _ => None,
+            })
+            .collect();
+        Operands { files, stdin_by_default: true, option_files: Vec::new() }
+    }
+
+    /// 命令是否会读取标准输入：没有文件参数，或显式给出 `-`
+    pub fn reads_stdin(&self, args: &[String]) -> bool {
+        if self.files.is_empty() {
+            return self.stdin_by_default;
+        }
+        self.stdin_dashes(args).next().is_some()
+    }
+
+    /// 命令读取的所有文件：文件参数和选项读取的文件（重复检测和结果缓存用来判断输入是否变化）
+    pub fn input_files<'a>(&'a self, args: &'a [String]) -> Vec<&'a str> {
+        self.files
+            .iter()
+            .map(|&i| args[i].as_str())
+            .chain(self.option_files.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// 显式表示标准输入的 `-` 参数的下标
+    pub fn stdin_dashes<'a>(&'a self, args: &'a [String]) -> impl Iterator<Item = usize> + 'a {
+        self.files.iter().copied().filter(move |&i| args[i] == "-")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn takes_n(name: &str) -> bool {
+        matches!(name, "-n" | "--lines")
+    }
+
+    #[test]
+    fn test_scan_short_cluster_with_inline_value() {
+        let scan = scan_args(&args(&["-qn5", "file"]), takes_n);
+        assert_eq!(
+            scan,
+            vec![
+                Arg::Opt { index: 0, name: "-q".to_string(), value: None },
+                Arg::Opt { index: 0, name: "-n".to_string(), value: Some("5".to_string()) },
+                Arg::Operand { index: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_value_in_next_arg() {
+        let scan = scan_args(&args(&["--lines", "5", "-n", "7", "a"]), takes_n);
+        assert_eq!(Operands::from_scan(&scan).files, vec![4]);
+    }
+
+    #[test]
+    fn test_scan_double_dash_and_stdin_dash() {
+        let list = args(&["-v", "--", "-n", "-"]);
+        let scan = scan_args(&list, takes_n);
+        let operands = Operands::from_scan(&scan);
+        assert_eq!(operands.files, vec![2, 3]);
+        assert_eq!(operands.stdin_dashes(&list).collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[test]
+    fn test_reads_stdin() {
+        let no_files = args(&["-n", "5"]);
+        let operands = Operands::from_scan(&scan_args(&no_files, takes_n));
+        assert!(operands.reads_stdin(&no_files));
+
+        let with_file = args(&["-n", "5", "file.txt"]);
+        let operands = Operands::from_scan(&scan_args(&with_file, takes_n));
+        assert!(!operands.reads_stdin(&with_file));
+
+        let with_dash = args(&["file.txt", "-"]);
+        let operands = Operands::from_scan(&scan_args(&with_dash, takes_n));
+        assert!(operands.reads_stdin(&with_dash));
+    }
+
+    #[test]
+    fn test_stdin_kind_does_not_panic() {
+        // 测试环境的 stdin 类型不确定，只确保不会 panic
+        let _ = stdin_kind();
+    }
+}
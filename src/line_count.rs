@@ -0,0 +1,307 @@
+//! tail/head 共用的行数强制策略
+
+use crate::line_opts::{self, Anchor, Count, Unit};
+
+use cursor_common::config::{Downstream, LineConfig};
+use cursor_common::follow::Window;
+use cursor_common::input::Operands;
+use cursor_common::mode::Mode;
+use cursor_common::policy::{describe_files, CommandPolicy, Enforcement, Rewrite};
+
+/// 行数强制规则：行数不足 min_lines（字节数不足 min_bytes）时强制改为最小值，
+/// 只替换计数本身，其他参数原样保留
+pub struct LineCountPolicy {
+    name: &'static str,
+    min_lines: u32,
+    min_bytes: u64,
+    /// 配置中指定的原始命令路径
+    path: String,
+    /// 输出接到下游程序时的处理方式
+    downstream: Downstream,
+    /// 强制模式
+    mode: Mode,
+    /// 是否支持 -f/-F 跟随（只有 tail 支持）
+    can_follow: bool,
+}
+
+impl LineCountPolicy {
+    pub fn new(name: &'static str, config: &LineConfig) -> Self {
+        LineCountPolicy {
+            name,
+            min_lines: config.min_lines,
+            min_bytes: config.min_bytes,
+            path: config.path.clone(),
+            downstream: config.downstream,
+            mode: config.mode,
+            can_follow: false,
+        }
+    }
+
+    /// 启用 -f/-F 跟随模式检测
+    pub fn with_follow(mut self) -> Self {
+        self.can_follow = true;
+        self
+    }
+
+    fn is_tail(&self) -> bool {
+        self.name == "tail"
+    }
+
+    fn minimum(&self, unit: Unit) -> u64 {
+        match unit {
+            Unit::Lines => u64::from(self.min_lines),
+            Unit::Bytes => self.min_bytes,
+        }
+    }
+
+    fn rule(&self, unit: Unit) -> String {
+        match unit {
+            Unit::Lines => format!("{}.min_lines", self.name),
+            Unit::Bytes => format!("{}.min_bytes", self.name),
+        }
+    }
+
+    /// 提示信息中展示的写法，例如 `tail -n 5`、`head -qn5`
+    fn display(&self, args: &[String], count: &Count) -> String {
+        match count.flag_index {
+            Some(flag) => format!("{} {} {}", self.name, args[flag], args[count.index]),
+            None => format!("{} {}", self.name, args[count.index]),
+        }
+    }
+}
+
+impl CommandPolicy for LineCountPolicy {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn original_override(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+
+    fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn downstream(&self) -> Downstream {
+        self.downstream
+    }
+
+    fn operands(&self, args: &[String]) -> Operands {
+        let parsed = line_opts::parse(args, self.is_tail());
+        Operands { files: parsed.files, stdin_by_default: true, option_files: Vec::new() }
+    }
+
+    fn describe_args(&self, args: &[String]) -> Vec<(String, String)> {
+        let parsed = line_opts::parse(args, self.is_tail());
+        let count = match &parsed.count {
+            Some(count) => {
+                let unit = if count.unit == Unit::Lines { "行" } else { "字节" };
+                let anchor = match count.anchor {
+                    Anchor::Last if self.is_tail() => "最后",
+                    Anchor::Last => "前",
+                    Anchor::FromStart => "从第 N 个开始",
+                    Anchor::AllButLast => "除最后 N 个以外",
+                };
+                format!("{} {} {}（{}）", anchor, count.value, unit, self.display(args, count))
+            }
+            None if parsed.invalid_count => "无法解析（交给原始命令报错）".to_string(),
+            None => "未指定".to_string(),
+        };
+        let mut described = vec![("计数".to_string(), count)];
+        if self.can_follow {
+            described.push(("跟随".to_string(), if parsed.follow { "是" } else { "否" }.to_string()));
+        }
+        let operands = Operands { files: parsed.files, stdin_by_default: true, option_files: Vec::new() };
+        described.push(("文件参数".to_string(), describe_files(args, &operands)));
+        described
+    }
+
+    fn follow_window(&self, args: &[String]) -> Window {
+        // 与 tail 一致：没有计数时输出最后 10 行
+        match line_opts::parse(args, self.is_tail()).count {
+            None => Window::LastLines(10),
+            Some(count) => match (count.unit, count.anchor) {
+                (Unit::Lines, Anchor::FromStart) => Window::FromLine(count.value),
+                (Unit::Bytes, Anchor::FromStart) => Window::FromByte(count.value),
+                (Unit::Lines, _) => Window::LastLines(count.value),
+                (Unit::Bytes, _) => Window::LastBytes(count.value),
+            },
+        }
+    }
+
+    fn follows(&self, args: &[String]) -> bool {
+        self.can_follow && line_opts::parse(args, self.is_tail()).follow
+    }
+
+    fn rewrite_args(&self, mut args: Vec<String>) -> Rewrite {
+        let parsed = line_opts::parse(&args, self.is_tail());
+        let mut enforcements = Vec::new();
+
+        match parsed.count {
+            // 只有"输出最后/前 N 个"才有不足的问题；tail +N、head -N 本来就输出大部分内容
+            Some(count) if count.anchor == Anchor::Last => {
+                let minimum = self.minimum(count.unit);
+                if count.value < minimum {
+                    let original = self.display(&args, &count);
+                    args[count.index].replace_range(count.range.clone(), &format!("{}{}", minimum, count.unit_suffix));
+                    let enforced = self.display(&args, &count);
+                    enforcements.push(Enforcement::new(&self.rule(count.unit), original, enforced));
+                }
+            }
+            Some(_) => {}
+            // 计数写错了就交给原始命令报错
+            None if parsed.invalid_count => {}
+            // 没有指定行数：在最前面添加 -n <min_lines>，不影响其他参数
+            None => {
+                let minimum = self.minimum(Unit::Lines);
+                enforcements.push(Enforcement::new(
+                    &self.rule(Unit::Lines),
+                    self.name,
+                    format!("{} -n {}", self.name, minimum),
+                ));
+                args.insert(0, minimum.to_string());
+                args.insert(0, "-n".to_string());
+            }
+        }
+
+        Rewrite { args, enforcements }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn policy(name: &'static str, min_lines: u32) -> LineCountPolicy {
+        LineCountPolicy::new(name, &LineConfig { min_lines, min_bytes: 4096, ..LineConfig::default() })
+    }
+
+    fn rewritten(name: &'static str, list: &[&str]) -> Vec<String> {
+        policy(name, 100).rewrite_args(args(list)).args
+    }
+
+    #[test]
+    fn test_raises_small_n() {
+        let policy = policy("tail", 100);
+        let rewrite = policy.rewrite_args(args(&["-n", "5"]));
+        assert_eq!(rewrite.args, args(&["-n", "100"]));
+        assert_eq!(rewrite.enforcements[0].original, "tail -n 5");
+        assert_eq!(rewrite.enforcements[0].rule, "tail.min_lines");
+    }
+
+    #[test]
+    fn test_raises_legacy_count() {
+        let policy = policy("head", 100);
+        let rewrite = policy.rewrite_args(args(&["-3"]));
+        assert_eq!(rewrite.args, args(&["-100"]));
+        // 带倍数后缀的旧式写法换算成字节，不能留下后缀
+        assert_eq!(rewritten("tail", &["-2k", "a.log"]), args(&["-4096c", "a.log"]));
+        assert_eq!(rewritten("tail", &["-2kf"]), args(&["-4096cf"]));
+        assert_eq!(rewritten("tail", &["-8k"]), args(&["-8k"]));
+        assert_eq!(rewritten("tail", &["+3b"]), args(&["+3b"]));
+    }
+
+    #[test]
+    fn test_inserts_default_count() {
+        let policy = policy("head", 100);
+        let rewrite = policy.rewrite_args(Vec::new());
+        assert_eq!(rewrite.args, args(&["-n", "100"]));
+        assert_eq!(rewrite.enforcements.len(), 1);
+    }
+
+    #[test]
+    fn test_min_lines_is_configurable() {
+        let policy = policy("tail", 20);
+        let rewrite = policy.rewrite_args(args(&["-n", "5"]));
+        assert_eq!(rewrite.args, args(&["-n", "20"]));
+    }
+
+    #[test]
+    fn test_operands() {
+        let policy = policy("tail", 100);
+        let list = args(&["-n", "5", "app.log"]);
+        assert!(!policy.operands(&list).reads_stdin(&list));
+        let list = args(&["-n", "5"]);
+        assert!(policy.operands(&list).reads_stdin(&list));
+    }
+
+    #[test]
+    fn test_follows() {
+        let tail = policy("tail", 100).with_follow();
+        assert!(tail.follows(&args(&["-f", "app.log"])));
+        assert!(tail.follows(&args(&["-n", "5", "-F", "app.log"])));
+        assert!(tail.follows(&args(&["--follow=name", "app.log"])));
+        assert!(tail.follows(&args(&["-qf"])));
+        assert!(!tail.follows(&args(&["-n", "5", "app.log"])));
+        assert!(!policy("head", 100).follows(&args(&["-f"])));
+    }
+
+    #[test]
+    fn test_follow_window() {
+        let tail = policy("tail", 100).with_follow();
+        assert_eq!(tail.follow_window(&args(&["-f"])), Window::LastLines(10));
+        assert_eq!(tail.follow_window(&args(&["-f", "-n", "5"])), Window::LastLines(5));
+        assert_eq!(tail.follow_window(&args(&["-f", "-n", "+3"])), Window::FromLine(3));
+        assert_eq!(tail.follow_window(&args(&["-fc", "1K"])), Window::LastBytes(1024));
+        assert_eq!(tail.follow_window(&args(&["-f", "-c", "+2"])), Window::FromByte(2));
+    }
+
+    #[test]
+    fn test_preserves_other_flags() {
+        assert_eq!(rewritten("head", &["-qn5", "a", "b"]), args(&["-qn100", "a", "b"]));
+        assert_eq!(rewritten("tail", &["-v", "--lines=5", "a"]), args(&["-v", "--lines=100", "a"]));
+        assert_eq!(rewritten("tail", &["-z", "--lines", "5"]), args(&["-z", "--lines", "100"]));
+        assert_eq!(rewritten("tail", &["-n", "-5"]), args(&["-n", "-100"]));
+        assert_eq!(rewritten("tail", &["-5f", "a"]), args(&["-100f", "a"]));
+    }
+
+    #[test]
+    fn test_signed_counts_are_not_enforced() {
+        assert_eq!(rewritten("tail", &["-n", "+5", "a"]), args(&["-n", "+5", "a"]));
+        assert_eq!(rewritten("head", &["-n", "-5", "a"]), args(&["-n", "-5", "a"]));
+    }
+
+    #[test]
+    fn test_bytes_use_min_bytes() {
+        let rewrite = policy("head", 100).rewrite_args(args(&["-c", "1K", "a"]));
+        assert_eq!(rewrite.args, args(&["-c", "4096", "a"]));
+        assert_eq!(rewrite.enforcements[0].rule, "head.min_bytes");
+        assert_eq!(rewritten("head", &["--bytes=1M"]), args(&["--bytes=1M"]));
+    }
+
+    #[test]
+    fn test_inserts_count_before_files_and_flags() {
+        assert_eq!(rewritten("tail", &["-v", "app.log"]), args(&["-n", "100", "-v", "app.log"]));
+    }
+
+    #[test]
+    fn test_invalid_count_is_left_to_command() {
+        let rewrite = policy("tail", 100).rewrite_args(args(&["-n", "abc"]));
+        assert_eq!(rewrite.args, args(&["-n", "abc"]));
+        assert!(rewrite.enforcements.is_empty());
+    }
+
+    #[test]
+    fn test_keeps_large_count() {
+        let policy = policy("tail", 100);
+        let rewrite = policy.rewrite_args(args(&["-n", "500"]));
+        assert_eq!(rewrite.args, args(&["-n", "500"]));
+        assert!(rewrite.enforcements.is_empty());
+    }
+
+    #[test]
+    fn test_describe_args() {
+        let described = policy("tail", 100).with_follow().describe_args(&args(&["-qn5", "-f", "app.log"]));
+        assert_eq!(described[0].1, "最后 5 行（tail -qn5）");
+        assert_eq!(described[1].1, "是");
+        assert_eq!(described[2].1, "app.log");
+        let described = policy("head", 100).describe_args(&args(&["-c", "1K"]));
+        assert_eq!(described[0].1, "前 1024 字节（head -c 1K）");
+        assert_eq!(described[1].1, "无（读取标准输入）");
+    }
+}
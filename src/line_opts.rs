@@ -0,0 +1,378 @@
+//! tail/head 参数解析（兼容 GNU 和 BSD 写法）
+//!
+//! 支持的计数写法：
+//! - `-n 5`、`-n5`、`--lines=5`、`--lines 5`、`-c 1K`、`--bytes=2MiB`
+//! - 组合短选项：`-qn5`、`-vc 10`
+//! - 带符号的计数：`tail -n +5`（从第 5 行开始）、`head -n -5`（除最后 5 行外全部）
+//! - 旧式写法：`-5`、`-5c`、`-5f`（tail）、`tail +5`
+//!
+//! 解析结果记录计数值在参数中的准确位置，强制规则只替换这个数字，其余参数原样保留。
+
+use std::ops::Range;
+
+/// 计数单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Lines,
+    Bytes,
+}
+
+/// 计数的含义
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    /// 普通计数：tail 输出最后 N 个，head 输出前 N 个
+    Last,
+    /// `tail -n +N`：从第 N 个开始输出到结尾
+    FromStart,
+    /// `head -n -N`：输出除最后 N 个以外的全部
+    AllButLast,
+}
+
+/// 解析出的计数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Count {
+    pub unit: Unit,
+    pub anchor: Anchor,
+    /// 换算后缀后的数值（`1K` → 1024）
+    pub value: u64,
+    /// 数值所在参数的下标
+    pub index: usize,
+    /// 数值（数字和后缀，不含符号）在该参数中的字节范围
+    pub range: Range<usize>,
+    /// 数值写在选项后面的单独参数中（`-n 5`）时，选项所在的下标
+    pub flag_index: Option<usize>,
+    /// 替换数值时追加在后面的单位：旧式 `-2k` 的范围包含倍数后缀，换算成字节后要写成 `-4096c`
+    pub unit_suffix: &'static str,
+}
+
+/// tail/head 参数的解析结果
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LineArgs {
+    /// 最后一个有效的计数（与 GNU 一致，后出现的覆盖前面的）
+    pub count: Option<Count>,
+    /// 给出了计数但无法解析（交给原始命令报错，不做强制）
+    pub invalid_count: bool,
+    /// 是否有 -f/-F/--follow
+    pub follow: bool,
+    /// 文件参数的下标
+    pub files: Vec<usize>,
+}
+
+/// 解析 tail（`is_tail` 为 true）或 head 的参数
+pub fn parse(args: &[String], is_tail: bool) -> LineArgs {
+    let mut parsed = LineArgs::default();
+    let mut i = 0;
+    let mut only_operands = false;
+
+    while i < args.len() {
+        let arg = &args[i];
+        let index = i;
+        i += 1;
+
+        if only_operands || arg == "-" {
+            parsed.files.push(index);
+            continue;
+        }
+        if arg == "--" {
+            only_operands = true;
+            continue;
+        }
+
+        // 旧式 tail +5：只在第一个参数位置有效
+        if let Some(rest) = arg.strip_prefix('+') {
+            if is_tail && index == 0 && rest.starts_with(|c: char| c.is_ascii_digit()) {
+                parse_legacy(&mut parsed, arg, index, Anchor::FromStart);
+            } else {
+                parsed.files.push(index);
+            }
+            continue;
+        }
+        if !arg.starts_with('-') {
+            parsed.files.push(index);
+            continue;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, _)) => (name, Some(name.len() + 3)),
+                None => (long, None),
+            };
+            match name {
+                "lines" | "bytes" => {
+                    let unit = if name == "lines" { Unit::Lines } else { Unit::Bytes };
+                    match inline {
+                        Some(start) => set_count(&mut parsed, args, index, start, unit, is_tail, None),
+                        None if i < args.len() => {
+                            set_count(&mut parsed, args, i, 0, unit, is_tail, Some(index));
+                            i += 1;
+                        }
+                        None => parsed.invalid_count = true,
+                    }
+                }
+                "follow" => parsed.follow = true,
+                "pid" | "sleep-interval" | "max-unchanged-stats" if inline.is_none() => i += 1,
+                _ => {}
+            }
+            continue;
+        }
+
+        // 旧式 -5、-5c、-5f
+        if arg[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            parse_legacy(&mut parsed, arg, index, Anchor::Last);
+            continue;
+        }
+
+        // 短选项组合：-qn5、-vc 10、-fs 2
+        for (pos, c) in arg.char_indices().skip(1) {
+            let rest = pos + c.len_utf8();
+            match c {
+                'n' | 'c' => {
+                    let unit = if c == 'n' { Unit::Lines } else { Unit::Bytes };
+                    if rest < arg.len() {
+                        set_count(&mut parsed, args, index, rest, unit, is_tail, None);
+                    } else if i < args.len() {
+                        set_count(&mut parsed, args, i, 0, unit, is_tail, Some(index));
+                        i += 1;
+                    } else {
+                        parsed.invalid_count = true;
+                    }
+                    break;
+                }
+                's' => {
+                    if rest == arg.len() {
+                        i += 1;
+                    }
+                    break;
+                }
+                'f' | 'F' => parsed.follow = true,
+                _ => {}
+            }
+        }
+    }
+    parsed
+}
+
+/// 解析 args[index][start..] 中的计数并记录为当前计数
+fn set_count(
+    parsed: &mut LineArgs,
+    args: &[String],
+    index: usize,
+    start: usize,
+    unit: Unit,
+    is_tail: bool,
+    flag_index: Option<usize>,
+) {
+    let text = &args[index][start..];
+    let (anchor, digits_start) = match text.chars().next() {
+        Some('+') if is_tail => (Anchor::FromStart, 1),
+        Some('-') if !is_tail => (Anchor::AllButLast, 1),
+        Some('+') | Some('-') => (Anchor::Last, 1),
+        _ => (Anchor::Last, 0),
+    };
+    match parse_size(&text[digits_start..]) {
+        Some(value) => {
+            parsed.count = Some(Count {
+                unit,
+                anchor,
+                value,
+                index,
+                range: start + digits_start..args[index].len(),
+                flag_index,
+                unit_suffix: "",
+            });
+            parsed.invalid_count = false;
+        }
+        None => {
+            parsed.count = None;
+            parsed.invalid_count = true;
+        }
+    }
+}
+
+/// 解析旧式 `-5`、`-5c`、`-5l`、`-5f`、`+5`
+fn parse_legacy(parsed: &mut LineArgs, arg: &str, index: usize, anchor: Anchor) {
+    let digits_end = 1 + arg[1..].find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len() - 1);
+    let mut unit = Unit::Lines;
+    let mut multiplier = 1;
+    for c in arg[digits_end..].chars() {
+        match c {
+            'c' => unit = Unit::Bytes,
+            'b' => {
+                unit = Unit::Bytes;
+                multiplier = 512;
+            }
+            'k' => {
+                unit = Unit::Bytes;
+                multiplier = 1024;
+            }
+            'm' => {
+                unit = Unit::Bytes;
+                multiplier = 1024 * 1024;
+            }
+            'l' => unit = Unit::Lines,
+            'f' => parsed.follow = true,
+            _ => {}
+        }
+    }
+    // 倍数后缀紧跟在数字后面，替换时一起替换，否则 `-2k` 会变成 `-4096k`
+    let multiplied = arg[digits_end..].starts_with(['b', 'k', 'm']);
+    match arg[1..digits_end].parse::<u64>() {
+        Ok(value) => {
+            parsed.count = Some(Count {
+                unit,
+                anchor,
+                value: value.saturating_mul(multiplier),
+                index,
+                range: 1..digits_end + usize::from(multiplied),
+                flag_index: None,
+                unit_suffix: if multiplied { "c" } else { "" },
+            });
+            parsed.invalid_count = false;
+        }
+        Err(_) => parsed.invalid_count = true,
+    }
+}
+
+/// 解析带后缀的数值：`5`、`1K`、`2KB`、`3MiB`、`4b`
+pub fn parse_size(text: &str) -> Option<u64> {
+    let digits_end = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    if digits_end == 0 {
+        return None;
+    }
+    let number: u64 = text[..digits_end].parse().ok()?;
+    let suffix = &text[digits_end..];
+    let multiplier: u64 = match suffix {
+        "" => 1,
+        "b" => 512,
+        _ => {
+            let (power, rest) = (suffix.get(..1)?, &suffix[1..]);
+            let exponent = match power {
+                "k" | "K" => 1,
+                "M" => 2,
+                "G" => 3,
+                "T" => 4,
+                "P" => 5,
+                "E" => 6,
+                _ => return None,
+            };
+            let base: u64 = match rest {
+                "" | "iB" => 1024,
+                "B" => 1000,
+                _ => return None,
+            };
+            base.checked_pow(exponent)?
+        }
+    };
+    Some(number.saturating_mul(multiplier))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn count_of(list: &[&str], is_tail: bool) -> Count {
+        parse(&args(list), is_tail).count.expect("应该解析出计数")
+    }
+
+    #[test]
+    fn test_short_forms() {
+        let count = count_of(&["-n", "5"], true);
+        assert_eq!((count.value, count.index, count.flag_index), (5, 1, Some(0)));
+        let count = count_of(&["-n5"], true);
+        assert_eq!((count.value, count.index, count.range), (5, 0, 2..3));
+    }
+
+    #[test]
+    fn test_long_forms() {
+        let count = count_of(&["--lines=5", "f"], false);
+        assert_eq!((count.value, count.range), (5, 8..9));
+        let count = count_of(&["--lines", "7"], false);
+        assert_eq!((count.value, count.index), (7, 1));
+        let count = count_of(&["--bytes=2KB"], false);
+        assert_eq!((count.unit, count.value), (Unit::Bytes, 2000));
+    }
+
+    #[test]
+    fn test_signed_counts() {
+        assert_eq!(count_of(&["-n", "+5"], true).anchor, Anchor::FromStart);
+        assert_eq!(count_of(&["-n", "-5"], false).anchor, Anchor::AllButLast);
+        assert_eq!(count_of(&["-n", "-5"], true).anchor, Anchor::Last);
+        assert_eq!(count_of(&["-n", "+5"], false).anchor, Anchor::Last);
+        assert_eq!(count_of(&["-n", "+5"], true).range, 1..2);
+    }
+
+    #[test]
+    fn test_byte_suffixes() {
+        assert_eq!(parse_size("1K"), Some(1024));
+        assert_eq!(parse_size("1k"), Some(1024));
+        assert_eq!(parse_size("1KiB"), Some(1024));
+        assert_eq!(parse_size("1MB"), Some(1_000_000));
+        assert_eq!(parse_size("2b"), Some(1024));
+        assert_eq!(parse_size("x"), None);
+        assert_eq!(parse_size("5Q"), None);
+        assert_eq!(parse_size("5é"), None);
+        let count = count_of(&["-c", "1K"], true);
+        assert_eq!((count.unit, count.value, count.range), (Unit::Bytes, 1024, 0..2));
+    }
+
+    #[test]
+    fn test_combined_flags() {
+        let parsed = parse(&args(&["-qn5", "-v", "-z", "a.log"]), false);
+        let count = parsed.count.unwrap();
+        assert_eq!((count.value, count.index, count.range), (5, 0, 3..4));
+        assert_eq!(parsed.files, vec![3]);
+        let parsed = parse(&args(&["-fn", "3", "a.log"]), true);
+        assert!(parsed.follow);
+        assert_eq!(parsed.count.unwrap().value, 3);
+        assert_eq!(parsed.files, vec![2]);
+    }
+
+    #[test]
+    fn test_legacy_forms() {
+        let count = count_of(&["-5"], false);
+        assert_eq!((count.value, count.range), (5, 1..2));
+        let parsed = parse(&args(&["-20f", "a.log"]), true);
+        assert!(parsed.follow);
+        assert_eq!(parsed.count.unwrap().value, 20);
+        assert_eq!(count_of(&["-5c"], true).unit, Unit::Bytes);
+        assert_eq!(count_of(&["+5"], true).anchor, Anchor::FromStart);
+        // 倍数后缀算在替换范围内
+        let count = count_of(&["-2k"], true);
+        assert_eq!((count.unit, count.value, count.range, count.unit_suffix), (Unit::Bytes, 2048, 1..3, "c"));
+        let count = count_of(&["+3b"], true);
+        assert_eq!((count.anchor, count.value, count.range), (Anchor::FromStart, 1536, 1..3));
+        // head 没有 + 写法，+5 是文件名
+        assert_eq!(parse(&args(&["+5"]), false).files, vec![0]);
+    }
+
+    #[test]
+    fn test_last_count_wins() {
+        let count = count_of(&["-n", "5", "-c", "10"], true);
+        assert_eq!((count.unit, count.value), (Unit::Bytes, 10));
+    }
+
+    #[test]
+    fn test_value_options_are_skipped() {
+        let parsed = parse(&args(&["--pid", "123", "-s", "2", "--sleep-interval=3", "a.log"]), true);
+        assert_eq!(parsed.files, vec![5]);
+        assert!(parsed.count.is_none());
+    }
+
+    #[test]
+    fn test_invalid_count() {
+        let parsed = parse(&args(&["-n", "abc"]), true);
+        assert!(parsed.count.is_none());
+        assert!(parsed.invalid_count);
+    }
+
+    #[test]
+    fn test_double_dash() {
+        let parsed = parse(&args(&["--", "-n"]), true);
+        assert_eq!(parsed.files, vec![1]);
+        assert!(parsed.count.is_none());
+    }
+}
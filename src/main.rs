@@ -1,3 +1,171 @@
+mod captures;
+mod explain;
+mod grep;
+mod head;
+mod line_count;
+mod line_opts;
+mod stats;
+mod tail;
+
+use cursor_common::follow;
+use cursor_common::policy::CommandPolicy;
+
+use std::env;
+use std::path::Path;
+use std::process;
+
+/// 多合一二进制的名称（直接调用时的 argv[0]）
+const MULTICALL_NAME: &str = "cursor-guard";
+
+/// 包装器入口函数
+type AppletMain = fn(Vec<String>);
+
+/// 按当前配置构造包装策略
+type PolicyFactory = fn() -> Box<dyn CommandPolicy>;
+
+/// 已注册的包装命令：argv[0] 名称 → 入口函数
+/// 新增包装命令只需在这里登记，并创建同名符号链接
+const APPLETS: &[(&str, AppletMain)] = &[
+    ("grep", grep::run),
+    ("tail", tail::run),
+    ("head", head::run),
+    // 内部命令：tail -f 结束后在后台继续记录
+    (follow::RECORD_APPLET, run_follow_recorder),
+];
+
+/// 包装命令的策略（供 explain 等子命令使用）
+const POLICIES: &[(&str, PolicyFactory)] = &[
+    ("grep", grep::policy),
+    ("tail", tail::policy),
+    ("head", head::policy),
+];
+
+/// 只能通过 `cursor-guard <子命令>` 调用的管理命令
+const SUBCOMMANDS: &[(&str, AppletMain)] = &[("explain", run_explain), ("stats", run_stats), ("captures", run_captures)];
+
 fn main() {
-    println!("Hello, world!");
+    let mut argv = env::args();
+    let argv0 = argv.next().unwrap_or_default();
+    let mut args: Vec<String> = argv.collect();
+
+    // 像 busybox 一样根据 argv[0] 决定运行哪个包装器
+    let mut name = applet_name(&argv0).to_string();
+
+    // 直接调用 cursor-guard 时，第一个参数作为命令名：cursor-guard grep foo
+    if name == MULTICALL_NAME {
+        if args.is_empty() {
+            print_usage();
+            process::exit(1);
+        }
+        name = args.remove(0);
+        if let Some(run) = find_subcommand(&name) {
+            run(args);
+            return;
+        }
+    }
+
+    match find_applet(&name) {
+        Some(run) => run(args),
+        None => {
+            eprintln!("❌ {}: 未知的包装命令 '{}'", MULTICALL_NAME, name);
+            print_usage();
+            process::exit(127);
+        }
+    }
+}
+
+/// 从 argv[0] 中取出命令名（去掉目录部分）
+fn applet_name(argv0: &str) -> &str {
+    Path::new(argv0)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(argv0)
+}
+
+/// 查找命令名对应的包装器入口
+fn find_applet(name: &str) -> Option<AppletMain> {
+    APPLETS
+        .iter()
+        .find(|(applet, _)| *applet == name)
+        .map(|(_, run)| *run)
+}
+
+/// 查找子命令入口
+fn find_subcommand(name: &str) -> Option<AppletMain> {
+    SUBCOMMANDS
+        .iter()
+        .find(|(subcommand, _)| *subcommand == name)
+        .map(|(_, run)| *run)
+}
+
+/// 构造命令名对应的包装策略，命令名可以带目录（如 /usr/bin/grep）
+fn find_policy(name: &str) -> Option<Box<dyn CommandPolicy>> {
+    let name = applet_name(name);
+    POLICIES
+        .iter()
+        .find(|(applet, _)| *applet == name)
+        .map(|(_, factory)| factory())
+}
+
+fn run_explain(args: Vec<String>) {
+    process::exit(explain::run(args, find_policy));
+}
+
+fn run_stats(args: Vec<String>) {
+    process::exit(stats::run(args));
+}
+
+fn run_captures(args: Vec<String>) {
+    process::exit(captures::run(args));
+}
+
+fn run_follow_recorder(args: Vec<String>) {
+    process::exit(follow::run_recorder(&args));
+}
+
+fn print_usage() {
+    let names: Vec<&str> = APPLETS
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| !name.starts_with("__"))
+        .collect();
+    eprintln!("用法: {} <命令> [参数...]", MULTICALL_NAME);
+    eprintln!("      或创建符号链接: ln -s {} <命令>", MULTICALL_NAME);
+    eprintln!("支持的命令: {}", names.join(", "));
+    let subcommands: Vec<&str> = SUBCOMMANDS.iter().map(|(name, _)| *name).collect();
+    eprintln!("管理命令: {} {{{}}} ...", MULTICALL_NAME, subcommands.join(","));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_applet_name_strips_directory() {
+        assert_eq!(applet_name("/usr/local/bin/grep"), "grep");
+        assert_eq!(applet_name("tail"), "tail");
+        assert_eq!(applet_name("./cursor-guard"), MULTICALL_NAME);
+    }
+
+    #[test]
+    fn test_find_applet() {
+        assert!(find_applet("grep").is_some());
+        assert!(find_applet("head").is_some());
+        assert!(find_applet("tail").is_some());
+        assert!(find_applet("cat").is_none());
+    }
+
+    #[test]
+    fn test_subcommands_are_not_applets() {
+        assert!(find_subcommand("explain").is_some());
+        assert!(find_applet("explain").is_none());
+    }
+
+    #[test]
+    fn test_every_wrapper_has_a_policy() {
+        for (name, _) in APPLETS.iter().filter(|(name, _)| !name.starts_with("__")) {
+            assert_eq!(find_policy(name).unwrap().name(), *name);
+        }
+        assert_eq!(find_policy("/usr/bin/grep").unwrap().name(), "grep");
+    }
 }
@@ -0,0 +1,108 @@
+//! 强制模式
+//!
+//! 同一套规则可以按不同力度执行：
+//! - off：包装器不做任何事
+//! - observe：只记录本应触发的规则，不改写、不提示
+//! - warn：提示建议的写法，但按原样执行
+//! - enforce：改写参数（默认）
+//! - strict：参数不足时拒绝执行，返回非零退出码
+
+use crate::config;
+use crate::policy::Enforcement;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// strict 模式拒绝执行时的退出码
+pub const STRICT_EXIT_CODE: i32 = 2;
+
+/// observe 模式的记录文件名（位于保存目录中）
+pub const OBSERVE_LOG_NAME: &str = "observe.log";
+
+/// 强制模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// 使用全局设置（只用于各包装器）
+    Inherit,
+    Off,
+    Observe,
+    Warn,
+    #[default]
+    Enforce,
+    Strict,
+}
+
+impl Mode {
+    /// 包装器的设置为 inherit 时使用全局设置
+    pub fn or(self, global: Mode) -> Mode {
+        match (self, global) {
+            (Mode::Inherit, Mode::Inherit) => Mode::Enforce,
+            (Mode::Inherit, global) => global,
+            (own, _) => own,
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Mode::Inherit => "inherit",
+            Mode::Off => "off",
+            Mode::Observe => "observe",
+            Mode::Warn => "warn",
+            Mode::Enforce => "enforce",
+            Mode::Strict => "strict",
+        };
+        f.write_str(name)
+    }
+}
+
+/// observe 模式：把本应触发的规则追加到保存目录下的 observe.log
+pub fn record_observation(name: &str, args: &[String], enforcement: &Enforcement) -> io::Result<()> {
+    let dir = &config::global().capture.dir;
+    fs::create_dir_all(dir)?;
+    let mut log = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(Path::new(dir).join(OBSERVE_LOG_NAME))?;
+    let agent = config::global().agent.as_ref().map_or("-", |found| found.agent.as_str());
+    writeln!(log, "{}", observation_line(agent, name, args, enforcement))
+}
+
+fn observation_line(agent: &str, name: &str, args: &[String], enforcement: &Enforcement) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{} -> {}\t{} {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        agent,
+        name,
+        enforcement.rule,
+        enforcement.original,
+        enforcement.enforced,
+        name,
+        args.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrapper_mode_overrides_global() {
+        assert_eq!(Mode::Inherit.or(Mode::Warn), Mode::Warn);
+        assert_eq!(Mode::Strict.or(Mode::Warn), Mode::Strict);
+        assert_eq!(Mode::Inherit.or(Mode::Inherit), Mode::Enforce);
+    }
+
+    #[test]
+    fn test_observation_line() {
+        let enforcement = Enforcement::new("tail.min_lines", "tail -n 5", "tail -n 100");
+        let line = observation_line("cursor", "tail", &["-n".to_string(), "5".to_string(), "app.log".to_string()], &enforcement);
+        assert!(line.ends_with("\tcursor\ttail\ttail.min_lines\ttail -n 5 -> tail -n 100\ttail -n 5 app.log"));
+    }
+}
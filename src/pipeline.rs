@@ -0,0 +1,157 @@
+//! 判断命令输出是否接到了下游程序
+//!
+//! 强制规则是为 Agent 直接阅读的输出设计的。输出接到下游程序时改写参数会改变结果：
+//! `grep err log | wc -l` 会数进上下文行，`VERSION=$(head -1 VERSION)` 会拿到 100 行。
+//!
+//! 标准输出是管道时，通过 `/proc` 找到管道的读端进程：
+//! - 读端是自己的祖先且不是 shell：Agent 在读取输出，命令处在管道末端
+//! - 读端是祖先 shell：命令替换 `$(...)`，输出交给 shell 变量
+//! - 读端是其他进程：管道中的下游命令
+//!
+//! 终端、普通文件、套接字（shell 的管道不用套接字）以及无法判断的情况都视为管道末端，
+//! 保持原有的强制行为。
+//!
+//! 标准输入、标准输出管道的两端在一次调用中只扫描一次 `/proc`（见 [`pipes`]），
+//! 保存管道输入时收集上游命令也使用同一份结果。
+
+use crate::procfs::{PipeEnds, ProcFs};
+
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsFd, BorrowedFd};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::process;
+use std::sync::OnceLock;
+
+/// 常见 shell 的进程名
+const SHELLS: &[&str] = &["sh", "bash", "dash", "zsh", "ksh", "mksh", "ash", "fish", "busybox"];
+
+/// 命令在管道中的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    /// 输出直接给 Agent（或终端、文件）
+    End,
+    /// 输出接到下游程序或命令替换
+    Downstream,
+}
+
+/// 当前进程标准输入、标准输出的管道及其两端
+#[derive(Debug, Clone, Default)]
+pub struct Pipes {
+    /// 标准输入是管道时的 inode
+    pub stdin: Option<u64>,
+    /// 标准输出是管道时的 inode
+    pub stdout: Option<u64>,
+    /// 当前会话中打开了这两个管道的进程
+    pub ends: PipeEnds,
+}
+
+/// 当前进程的管道，首次调用时扫描 `/proc`，之后复用结果
+pub fn pipes() -> &'static Pipes {
+    static PIPES: OnceLock<Pipes> = OnceLock::new();
+    PIPES.get_or_init(|| {
+        let stdout = if atty::is(atty::Stream::Stdout) { None } else { fifo_inode(io::stdout().as_fd()) };
+        scan(&ProcFs::system(), process::id(), fifo_inode(io::stdin().as_fd()), stdout)
+    })
+}
+
+/// fd 是管道时返回管道的 inode
+fn fifo_inode(fd: BorrowedFd) -> Option<u64> {
+    let meta = fd.try_clone_to_owned().and_then(|fd| File::from(fd).metadata()).ok()?;
+    meta.file_type().is_fifo().then(|| meta.ino())
+}
+
+/// 只在当前进程所在的会话中查找管道两端；扫描失败时两端都为空
+pub(crate) fn scan(proc: &ProcFs, pid: u32, stdin: Option<u64>, stdout: Option<u64>) -> Pipes {
+    let inodes: Vec<u64> = stdin.into_iter().chain(stdout).collect();
+    let session = proc.stat(pid).map(|stat| stat.session);
+    let ends = proc.pipe_ends(&inodes, session).unwrap_or_default();
+    Pipes { stdin, stdout, ends }
+}
+
+/// 判断当前进程标准输出的去向
+pub fn stdout_position() -> Position {
+    classify(&ProcFs::system(), process::id(), pipes())
+}
+
+/// 根据管道读端进程判断位置
+fn classify(proc: &ProcFs, pid: u32, pipes: &Pipes) -> Position {
+    let Some(inode) = pipes.stdout else {
+        return Position::End;
+    };
+    let ancestors = proc.ancestors(pid);
+
+    // 找不到读端（例如无权查看）时无法判断，保持原有行为
+    let downstream = pipes.ends.readers(inode).into_iter().filter(|&r| r != pid).any(|reader| {
+        !ancestors.contains(&reader) || proc.comm(reader).is_some_and(|comm| is_shell(&comm))
+    });
+    if downstream {
+        Position::Downstream
+    } else {
+        Position::End
+    }
+}
+
+pub(crate) fn is_shell(comm: &str) -> bool {
+    let name = comm.trim_start_matches('-');
+    SHELLS.contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procfs::fake::FakeProc;
+
+    fn position(proc: &ProcFs, pid: u32, inode: u64) -> Position {
+        classify(proc, pid, &scan(proc, pid, None, Some(inode)))
+    }
+
+    /// node(10) -> bash(20) -> grep(30)，grep 的标准输出是管道 500
+    fn agent_shell(name: &str) -> FakeProc {
+        let fake = FakeProc::new(name);
+        fake.process(1, 0, "init")
+            .process(10, 1, "node")
+            .process(20, 10, "bash")
+            .process(30, 20, "grep")
+            .pipe_fd(30, 1, 500, true);
+        fake
+    }
+
+    #[test]
+    fn test_agent_reads_output() {
+        let fake = agent_shell("agent");
+        fake.pipe_fd(10, 21, 500, false);
+        assert_eq!(position(&ProcFs::new(&fake.root), 30, 500), Position::End);
+    }
+
+    #[test]
+    fn test_sibling_in_pipeline() {
+        // grep err log | wc -l
+        let fake = agent_shell("sibling");
+        fake.process(31, 20, "wc").pipe_fd(31, 0, 500, false);
+        assert_eq!(position(&ProcFs::new(&fake.root), 30, 500), Position::Downstream);
+    }
+
+    #[test]
+    fn test_command_substitution() {
+        // VERSION=$(head -1 VERSION)：父 shell 读取命令替换的输出
+        let fake = agent_shell("subst");
+        fake.pipe_fd(20, 3, 500, false);
+        assert_eq!(position(&ProcFs::new(&fake.root), 30, 500), Position::Downstream);
+    }
+
+    #[test]
+    fn test_unknown_reader_keeps_enforcement() {
+        let fake = agent_shell("unknown");
+        assert_eq!(position(&ProcFs::new(&fake.root), 30, 500), Position::End);
+        let missing = ProcFs::new("/tmp/cursor_test_procfs/none");
+        assert_eq!(position(&missing, 30, 500), Position::End);
+    }
+
+    #[test]
+    fn test_is_shell() {
+        assert!(is_shell("bash"));
+        assert!(is_shell("-zsh"));
+        assert!(!is_shell("node"));
+    }
+}
@@ -0,0 +1,762 @@
+//! 声明式包装策略
+//!
+//! 每个包装命令只需实现 [`CommandPolicy`]，描述参数改写、管道输入处理和提示信息，
+//! 检测环境、保存管道输入、执行原始命令的流程统一由 [`run_policy`] 完成。
+
+use crate::audit::{self, Record};
+use crate::bypass::{self, Bypass, BYPASS_ENV};
+use crate::cache::{self, Cache};
+use crate::capture;
+use crate::config::{self, Downstream};
+use crate::follow::{self, Budget};
+use crate::guard;
+use crate::input::{scan_args, stdin_kind, Operands};
+use crate::mode::{self, Mode, STRICT_EXIT_CODE};
+use crate::pipeline::{stdout_position, Position};
+use crate::repeat::{self, Entry, History, Repeats};
+use crate::resolve::resolve_original;
+use crate::session;
+use crate::{
+    create_capture_file, enforce_capture_limits, execute_original_command, lock_exclusive, print_enforcement_message,
+    print_refusal_message, print_warning_message, record_capture_meta, tee_stdin_to_command, Captured,
+};
+
+use chrono::{DateTime, Local, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// 一条被触发的强制规则
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Enforcement {
+    /// 规则名，例如 `grep.context`、`tail.min_lines`
+    pub rule: String,
+    /// 用户原本的写法，例如 `tail -n 5`
+    pub original: String,
+    /// 强制后的写法，例如 `tail -n 100`
+    pub enforced: String,
+}
+
+impl Enforcement {
+    pub fn new(rule: &str, original: impl Into<String>, enforced: impl Into<String>) -> Self {
+        Enforcement {
+            rule: rule.to_string(),
+            original: original.into(),
+            enforced: enforced.into(),
+        }
+    }
+}
+
+/// 参数改写结果
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Rewrite {
+    /// 改写后的参数（不含管道输入文件）
+    pub args: Vec<String>,
+    /// 改写过程中触发的规则
+    pub enforcements: Vec<Enforcement>,
+}
+
+impl Rewrite {
+    /// 不做任何改写
+    pub fn unchanged(args: Vec<String>) -> Self {
+        Rewrite { args, enforcements: Vec::new() }
+    }
+}
+
+/// 包装命令的策略描述
+pub trait CommandPolicy {
+    /// 命令名，同时也是 argv[0] 名称
+    fn name(&self) -> &str;
+
+    /// 在 PATH 中查找原始命令时依次尝试的名称（默认只有命令名本身）
+    fn original_names(&self) -> Vec<&str> {
+        vec![self.name()]
+    }
+
+    /// 配置中显式指定的原始命令路径
+    fn original_override(&self) -> Option<&str> {
+        None
+    }
+
+    /// 改写参数，返回改写后的参数和触发的规则
+    fn rewrite_args(&self, args: Vec<String>) -> Rewrite;
+
+    /// 解析文件参数，用来判断命令是否会读取标准输入
+    ///
+    /// 默认认为没有选项需要值，所有非选项参数都是文件
+    fn operands(&self, args: &[String]) -> Operands {
+        Operands::from_scan(&scan_args(args, |_| false))
+    }
+
+    /// 参数解析结果（`cursor-guard explain` 展示用），每项为 (名称, 值)
+    ///
+    /// 默认只列出文件参数
+    fn describe_args(&self, args: &[String]) -> Vec<(String, String)> {
+        vec![("文件参数".to_string(), describe_files(args, &self.operands(args)))]
+    }
+
+    /// 是否在转发管道输入的同时把它保存到临时文件
+    fn capture_stdin(&self) -> bool {
+        true
+    }
+
+    /// 是否为跟随模式（例如 `tail -f`），跟随模式在预算内边输出边保存，然后自动结束
+    fn follows(&self, _args: &[String]) -> bool {
+        false
+    }
+
+    /// 输出到终端时结果是否不同（例如 `grep --color=auto` 着色）
+    ///
+    /// 结果缓存通过管道读取原始命令的输出，这样的调用在终端中不缓存，避免丢失颜色
+    fn colors_terminal(&self, _args: &[String]) -> bool {
+        false
+    }
+
+    /// 跟随管道输入时输出的范围（不经过原始命令，需要自己按计数输出）
+    fn follow_window(&self, _args: &[String]) -> follow::Window {
+        follow::Window::All
+    }
+
+    /// 强制模式（默认使用全局设置）
+    fn mode(&self) -> Mode {
+        Mode::Inherit
+    }
+
+    /// 输出接到下游程序时的处理方式（默认使用 `[pipeline]` 中的设置）
+    fn downstream(&self) -> Downstream {
+        Downstream::Inherit
+    }
+
+    /// 管道输入临时文件的前缀
+    fn capture_prefix(&self) -> String {
+        format!("{}_input", self.name())
+    }
+
+    /// 打印规则触发提示（默认使用统一格式）
+    fn announce(&self, enforcement: &Enforcement) {
+        print_enforcement_message(&enforcement.original, &enforcement.enforced);
+    }
+}
+
+/// 文件参数的展示文本
+pub fn describe_files(args: &[String], operands: &Operands) -> String {
+    if operands.files.is_empty() {
+        return if operands.stdin_by_default { "无（读取标准输入）" } else { "无（不读取标准输入）" }.to_string();
+    }
+    let files: Vec<&str> = operands.files.iter().map(|&i| args[i].as_str()).collect();
+    files.join(" ")
+}
+
+/// 一次调用的执行方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// 不干预，直接执行原始命令
+    Passthrough(&'static str),
+    /// strict 模式下参数不足，拒绝执行
+    Refuse,
+    /// strict 模式下同一命令重复次数达到阈值，拒绝执行
+    RefuseRepeat,
+    /// 文件没有变化，直接输出缓存的结果
+    Replay,
+    /// 跟随模式，from_stdin 表示跟随管道输入
+    Follow { from_stdin: bool },
+    /// 边保存管道输入边执行
+    Capture,
+    /// 执行原始命令（参数可能已改写）
+    Run,
+}
+
+impl Action {
+    /// 审计日志中的名称
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Passthrough(_) => "passthrough",
+            Action::Refuse => "refuse",
+            Action::RefuseRepeat => "refuse-repeat",
+            Action::Follow { .. } => "follow",
+            Action::Capture => "capture",
+            Action::Run => "run",
+            Action::Replay => "replay",
+        }
+    }
+}
+
+/// 一次调用的执行计划：只做判断，不执行命令、不打印提示
+#[derive(Debug, Clone)]
+pub struct Plan {
+    /// 原始命令路径
+    pub original: PathBuf,
+    /// 强制模式（已合并全局设置）
+    pub mode: Mode,
+    /// 输出接到下游程序时的处理方式（已合并全局设置）
+    pub downstream: Downstream,
+    /// 标准输出的位置，None 表示无需检查
+    pub position: Option<Position>,
+    /// 命令是否读取标准输入
+    pub reads_stdin: bool,
+    /// 标准输入是否为管道
+    pub stdin_is_pipe: bool,
+    /// 规则匹配结果（无论是否生效）
+    pub rewrite: Rewrite,
+    /// 规则是否生效：Agent 执行、模式不是 off、没有绕过、输出在管道末端
+    pub rules_apply: bool,
+    /// 绕过强制规则的来源
+    pub bypass: Option<Bypass>,
+    /// 同一命令在时间窗口内之前的调用；None 表示不检查，或读取管道输入后才能判断
+    pub repeats: Option<Repeats>,
+    /// 结果缓存的键，None 表示不使用缓存
+    pub cache_key: Option<String>,
+    /// 用户输入的参数（已去掉 `--cg-raw`）
+    pub typed: Vec<String>,
+    /// 实际执行的参数
+    pub args: Vec<String>,
+    pub action: Action,
+}
+
+/// 为一次调用生成执行计划
+pub fn plan(policy: &dyn CommandPolicy, args: Vec<String>) -> io::Result<Plan> {
+    let config = config::global();
+    let original = resolve_original(&policy.original_names(), policy.original_override())?;
+    let mode = policy.mode().or(config.mode);
+    let downstream = policy.downstream().or(config.pipeline.downstream);
+    // `--cg-raw` 无论是否生效都不能传给原始命令
+    let (args, raw_flag) = bypass::strip_flag(args);
+    let rewrite = policy.rewrite_args(args.clone());
+
+    let mut plan = Plan {
+        original,
+        mode,
+        downstream,
+        position: None,
+        reads_stdin: false,
+        stdin_is_pipe: false,
+        rewrite,
+        rules_apply: false,
+        bypass: None,
+        repeats: None,
+        cache_key: None,
+        typed: args.clone(),
+        args,
+        action: Action::Run,
+    };
+
+    // 如果不是 AI Agent 执行的命令或包装器已关闭，直接调用原始命令
+    if config.agent.is_none() {
+        plan.action = Action::Passthrough("未检测到 AI Agent");
+        return Ok(plan);
+    }
+    if mode == Mode::Off {
+        plan.action = Action::Passthrough("mode = off");
+        return Ok(plan);
+    }
+    if raw_flag {
+        plan.bypass = Some(Bypass::Flag);
+    } else if bypass::env_covers(env::var(BYPASS_ENV).ok().as_deref(), policy.name()) {
+        plan.bypass = Some(Bypass::Env);
+    }
+    if let Some(bypass) = plan.bypass {
+        plan.action = Action::Passthrough(match bypass {
+            Bypass::Flag => "绕过：--cg-raw",
+            Bypass::Env => "绕过：CURSOR_GUARD_BYPASS",
+        });
+        return Ok(plan);
+    }
+
+    // 只有命令确实读取标准输入（没有文件参数或显式给出 -），且标准输入是管道时，
+    // 才保存管道输入；/dev/null 和普通文件重定向直接交给原始命令
+    let operands = policy.operands(&plan.typed);
+    plan.reads_stdin = operands.reads_stdin(&plan.typed);
+    plan.stdin_is_pipe = stdin_kind().is_pipe();
+    let capture = policy.capture_stdin() && plan.reads_stdin && plan.stdin_is_pipe;
+    let follows = policy.follows(&plan.typed);
+
+    // 输出接到下游程序时改写参数会改变下游拿到的结果，按配置只保存输入或完全不干预
+    if downstream != Downstream::Enforce {
+        plan.position = Some(stdout_position());
+    }
+    plan.rules_apply = plan.position != Some(Position::Downstream);
+    if !plan.rules_apply && downstream == Downstream::Passthrough {
+        plan.action = Action::Passthrough("输出接到下游程序（downstream = passthrough）");
+        return Ok(plan);
+    }
+    if plan.rules_apply {
+        match final_args(&plan.rewrite, &plan.typed, mode) {
+            Some(final_args) => plan.args = final_args,
+            None => {
+                plan.action = Action::Refuse;
+                return Ok(plan);
+            }
+        }
+    }
+
+    // 命令标识（重复检测和结果缓存共用）：管道输入要读取完才知道内容，其他情况执行前就能确定
+    let files = operands.input_files(&plan.typed);
+    let piped = plan.reads_stdin && plan.stdin_is_pipe;
+    let key = |args: &[String]| repeat::key(args, &current_dir(), &repeat::files_input(&files));
+    let now = Local::now().timestamp();
+
+    if checks_repeats(&plan) && !piped {
+        let repeats = History::new(&session::dir()).repeats(&key(&plan.args), now, &config.repeat);
+        let refuse = mode == Mode::Strict && config.repeat.refuse && repeats.reached(&config.repeat);
+        plan.repeats = Some(repeats);
+        if refuse {
+            plan.action = Action::RefuseRepeat;
+            return Ok(plan);
+        }
+    }
+
+    plan.action = if mode == Mode::Observe {
+        // observe 模式只记录，不保存输入也不限制跟随
+        Action::Run
+    } else if follows && (!plan.reads_stdin || plan.stdin_is_pipe) {
+        // warn 模式不改写参数，但不限制跟随会让 Agent 的会话一直挂起，同样按预算结束
+        Action::Follow { from_stdin: plan.reads_stdin }
+    } else if capture {
+        Action::Capture
+    } else {
+        Action::Run
+    };
+
+    // 文件没有变化时直接用上次的输出回答
+    let cacheable = config.cache.enabled
+        && mode != Mode::Observe
+        && !plan.reads_stdin
+        && !(atty::is(atty::Stream::Stdout) && policy.colors_terminal(&plan.args));
+    if plan.action == Action::Run && cacheable && cache::cacheable_files(&files) {
+        let cache_key = key(&plan.args);
+        if Cache::new(&cache::dir()).lookup(&cache_key, now, &config.cache).is_some() {
+            plan.action = Action::Replay;
+        }
+        plan.cache_key = Some(cache_key);
+    }
+    Ok(plan)
+}
+
+/// 运行任意包装策略，返回原始命令的退出码
+pub fn run_policy(policy: &dyn CommandPolicy, args: Vec<String>) -> i32 {
+    // 防止原始命令指回包装器导致无限递归
+    if let Err(e) = guard::check(policy.name(), config::global().guard.max_depth) {
+        eprintln!("❌ {}: {}", policy.name(), e);
+        return 126;
+    }
+
+    let started = Local::now();
+    let clock = Instant::now();
+    let plan = match plan(policy, args) {
+        Ok(plan) => plan,
+        Err(e) => {
+            eprintln!("❌ {}: {}", policy.name(), e);
+            return 127;
+        }
+    };
+    let (code, captured) = match extract_captures(policy, &plan) {
+        Some((run, _extracted)) => execute(policy, &run),
+        None => execute(policy, &plan),
+    };
+    track_repeats(policy, &plan, captured.as_ref());
+
+    // 只记录 Agent 的调用，用户手动执行的命令不记录
+    let config = config::global();
+    if config.audit.enabled && config.agent.is_some() {
+        let record = audit_record(policy, &plan, code, captured, started, clock.elapsed());
+        if let Err(e) = audit::append(&audit::log_path(&config.audit), &record, config.audit.max_bytes) {
+            eprintln!("⚠️  无法写入审计日志: {}", e);
+        }
+    }
+    code
+}
+
+/// 按计划打印提示并执行，返回退出码和保存的输入
+fn execute(policy: &dyn CommandPolicy, plan: &Plan) -> (i32, Option<Captured>) {
+    if plan.rules_apply {
+        report(policy, plan);
+    }
+    match plan.action {
+        Action::Passthrough(_) => (run_original(policy, &plan.original, &plan.args), None),
+        Action::Run | Action::Replay => (run_cached(policy, plan), None),
+        Action::Refuse => (STRICT_EXIT_CODE, None),
+        Action::RefuseRepeat => {
+            if let Some(repeats) = &plan.repeats {
+                repeat::print_repeat_message(&command_line(policy, &plan.args), repeats, &config::global().repeat, true);
+            }
+            (STRICT_EXIT_CODE, None)
+        }
+        Action::Follow { from_stdin } => {
+            if plan.mode == Mode::Warn {
+                follow::print_budget_warning(&Budget::from(&config::global().follow));
+            }
+            run_follow(policy, &plan.original, &plan.args, from_stdin)
+        }
+        // 边保存边转发，原始命令照常从标准输入读取
+        Action::Capture => match tee_stdin_to_command(&plan.original, policy.name(), &plan.args, &policy.capture_prefix(), &command_line(policy, &plan.args)) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("❌ 无法保存管道输入: {}", e);
+                (1, None)
+            }
+        },
+    }
+}
+
+/// 参数中有已压缩的保存文件时，返回改为读取解压后临时文件的计划；临时文件在 Extracted drop 时删除
+///
+/// 审计和重复检测仍然记录原来的参数
+///
+/// 未检测到 Agent、mode = off 和绕过时原样执行，保持与原始命令一致
+fn extract_captures(policy: &dyn CommandPolicy, plan: &Plan) -> Option<(Plan, capture::Extracted)> {
+    if matches!(plan.action, Action::Passthrough(_) | Action::Refuse | Action::RefuseRepeat | Action::Replay) {
+        return None;
+    }
+    let mut run = plan.clone();
+    let dir = Path::new(&config::global().capture.dir);
+    let extracted = match capture::extract_args(&mut run.args, dir) {
+        Ok(extracted) if !extracted.files.is_empty() => extracted,
+        Ok(_) => return None,
+        Err(e) => {
+            eprintln!("⚠️  无法解压保存文件: {}", e);
+            return None;
+        }
+    };
+    for (path, plain) in &extracted.files {
+        eprintln!("📦 {} 已压缩保存，{} 读取的是解压后的临时文件 {}", path, policy.name(), plain.display());
+    }
+    Some((run, extracted))
+}
+
+/// 执行原始命令；可以缓存时优先使用缓存，否则执行并保存结果
+fn run_cached(policy: &dyn CommandPolicy, plan: &Plan) -> i32 {
+    let Some(key) = &plan.cache_key else {
+        return run_original(policy, &plan.original, &plan.args);
+    };
+    let settings = &config::global().cache;
+    let cache = Cache::new(&cache::dir());
+    let now = Local::now().timestamp();
+    if plan.action == Action::Replay {
+        // 计划和执行之间缓存可能刚好过期，此时重新执行
+        if let Some(meta) = cache.lookup(key, now, settings) {
+            cache::print_replay_message(&meta, now);
+            return match cache.replay(key, &mut io::stdout()) {
+                Ok(_) => meta.exit_code,
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => meta.exit_code,
+                Err(e) => {
+                    eprintln!("❌ 无法读取结果缓存: {}", e);
+                    1
+                }
+            };
+        }
+    }
+    match cache.run_and_store(key, &plan.original, policy.name(), &plan.args, &command_line(policy, &plan.args), settings) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("❌ 无法执行 {}: {}", plan.original.display(), e);
+            1
+        }
+    }
+}
+
+/// 是否检查重复命令：只检查规则生效、会打印提示的调用
+fn checks_repeats(plan: &Plan) -> bool {
+    config::global().repeat.enabled && plan.rules_apply && plan.mode != Mode::Observe
+}
+
+/// 记录本次调用，重复次数达到阈值时打印提示
+fn track_repeats(policy: &dyn CommandPolicy, plan: &Plan, captured: Option<&Captured>) {
+    if !checks_repeats(plan) || !matches!(plan.action, Action::Run | Action::Replay | Action::Capture | Action::Follow { .. }) {
+        return;
+    }
+    let settings = &config::global().repeat;
+    let history = History::new(&session::dir());
+    let now = Local::now().timestamp();
+    let repeats = match (&plan.repeats, captured) {
+        (Some(repeats), _) => repeats.clone(),
+        (None, Some(Captured { sha256: Some(sha256), .. })) => {
+            let input = repeat::stdin_input(sha256);
+            history.repeats(&repeat::key(&plan.args, &current_dir(), &input), now, settings)
+        }
+        // 管道输入为空，或没有算出内容哈希
+        (None, _) => return,
+    };
+
+    let command = command_line(policy, &plan.args);
+    if repeats.reached(settings) {
+        repeat::print_repeat_message(&command, &repeats, settings, false);
+    }
+    let entry = Entry { time: now, key: repeats.key, command, capture: captured.map(|c| c.path.clone()) };
+    if let Err(e) = history.record(entry, settings) {
+        eprintln!("⚠️  无法写入会话历史: {}", e);
+    }
+}
+
+fn command_line(policy: &dyn CommandPolicy, args: &[String]) -> String {
+    format!("{} {}", policy.name(), args.join(" ")).trim_end().to_string()
+}
+
+fn current_dir() -> String {
+    env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default()
+}
+
+/// 生成审计记录
+fn audit_record(
+    policy: &dyn CommandPolicy,
+    plan: &Plan,
+    exit_code: i32,
+    captured: Option<Captured>,
+    started: DateTime<Local>,
+    elapsed: Duration,
+) -> Record {
+    let name = policy.name().to_string();
+    let command_line = |args: &[String]| std::iter::once(name.clone()).chain(args.iter().cloned()).collect();
+    Record {
+        timestamp: started.to_rfc3339_opts(SecondsFormat::Millis, false),
+        session: session::id(),
+        cwd: current_dir(),
+        agent: config::global().agent.as_ref().map(|found| found.agent.clone()),
+        command: name.clone(),
+        mode: plan.mode,
+        action: plan.action.label().to_string(),
+        bypass: plan.bypass.map(|bypass| bypass.to_string()),
+        argv: command_line(&plan.typed),
+        rewritten: (!matches!(plan.action, Action::Refuse | Action::RefuseRepeat)).then(|| command_line(&plan.args)),
+        rules: plan.rewrite.enforcements.clone(),
+        rules_applied: plan.rules_apply,
+        input_bytes: captured.as_ref().map(|c| c.bytes),
+        input_lines: captured.as_ref().map(|c| c.lines),
+        capture: captured.map(|c| c.path),
+        exit_code,
+        duration_ms: elapsed.as_millis() as u64,
+    }
+}
+
+/// 跟随模式：在预算内边输出边保存，然后打印摘要；到达预算时返回 0，tail 自己退出时返回它的退出码
+fn run_follow(policy: &dyn CommandPolicy, original: &Path, args: &[String], from_stdin: bool) -> (i32, Option<Captured>) {
+    let settings = &config::global().follow;
+    let budget = Budget::from(settings);
+    let limit = config::global().capture.max_total_bytes;
+    let (capture_path, file) = match create_capture_file(&format!("{}_follow", policy.name())) {
+        Ok(created) => created,
+        Err(e) => {
+            eprintln!("❌ 无法创建保存文件: {}", e);
+            return (1, None);
+        }
+    };
+    let origin = capture::Origin::detect();
+    let command = command_line(policy, args);
+    // 跟随期间锁住保存文件，其他调用清理保存目录时跳过它；返回时随文件关闭释放
+    if let Err(e) = lock_exclusive(&file) {
+        eprintln!("⚠️  无法锁定保存文件: {}", e);
+    }
+    let mut capture = capture::QuotaWriter::new(file, limit);
+
+    let result = if from_stdin {
+        follow::follow_stdin(&mut capture, budget, policy.follow_window(args))
+    } else {
+        follow::follow_command(original, policy.name(), args, &mut capture, budget)
+    };
+    let summary = match result {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("❌ 跟随失败: {}", e);
+            return (1, None);
+        }
+    };
+
+    // 超过配额时与管道输入一样不留下不完整的文件
+    if summary.quota_exceeded {
+        let _ = fs::remove_file(&capture_path);
+        capture::print_quota_message(limit);
+        return (summary.exit_code, None);
+    }
+
+    // 文件参数：到达预算后交给后台记录进程继续追加新内容，记录结束后由它写入元数据
+    let mut recording = None;
+    if !from_stdin && summary.reason != follow::StopReason::Eof && settings.record_secs > 0 {
+        let operands = policy.operands(args);
+        let files: Vec<String> = operands
+            .files
+            .iter()
+            .map(|&i| args[i].clone())
+            .filter(|f| f != "-")
+            .collect();
+        let started = capture::describe(Path::new(&capture_path), &origin, &command)
+            .and_then(|meta| follow::spawn_recorder(original, policy.name(), &files, &meta, settings.record_secs));
+        match started {
+            Ok(()) => recording = Some(settings.record_secs),
+            Err(e) => eprintln!("⚠️  无法启动后台记录: {}", e),
+        }
+    }
+
+    follow::print_follow_summary(&summary, &capture_path, recording);
+    let sha256 = match recording {
+        Some(_) => None,
+        None => record_capture_meta(&capture_path, &origin, &command),
+    };
+    enforce_capture_limits(&capture_path);
+    (summary.exit_code, Some(Captured { path: capture_path, bytes: summary.bytes, lines: summary.lines, sha256 }))
+}
+
+/// 按强制模式决定实际执行的参数；strict 模式拒绝执行时返回 None
+fn final_args(rewrite: &Rewrite, typed: &[String], mode: Mode) -> Option<Vec<String>> {
+    match mode {
+        Mode::Observe | Mode::Warn => Some(typed.to_vec()),
+        Mode::Strict if !rewrite.enforcements.is_empty() => None,
+        _ => Some(rewrite.args.clone()),
+    }
+}
+
+/// 按强制模式打印提示或记录触发的规则
+fn report(policy: &dyn CommandPolicy, plan: &Plan) {
+    for enforcement in &plan.rewrite.enforcements {
+        match plan.mode {
+            Mode::Observe => {
+                if let Err(e) = mode::record_observation(policy.name(), &plan.typed, enforcement) {
+                    eprintln!("⚠️  无法写入 observe 记录: {}", e);
+                }
+            }
+            Mode::Warn => print_warning_message(&enforcement.original, &enforcement.enforced),
+            Mode::Strict => print_refusal_message(&enforcement.original, &enforcement.enforced),
+            _ => policy.announce(enforcement),
+        }
+    }
+}
+
+fn run_original(policy: &dyn CommandPolicy, original: &Path, args: &[String]) -> i32 {
+    match execute_original_command(original, policy.name(), args) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("❌ 无法执行 {}: {}", original.display(), e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct AppendPolicy;
+
+    impl CommandPolicy for AppendPolicy {
+        fn name(&self) -> &str {
+            "demo"
+        }
+
+        fn rewrite_args(&self, mut args: Vec<String>) -> Rewrite {
+            args.insert(0, "-n".to_string());
+            Rewrite {
+                args,
+                enforcements: vec![Enforcement::new("demo.flag", "demo", "demo -n")],
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_capture_prefix() {
+        assert_eq!(AppendPolicy.capture_prefix(), "demo_input");
+        assert!(AppendPolicy.capture_stdin());
+    }
+
+    #[test]
+    fn test_default_original_names() {
+        assert_eq!(AppendPolicy.original_names(), vec!["demo"]);
+        assert_eq!(AppendPolicy.original_override(), None);
+    }
+
+    #[test]
+    fn test_final_args_keeps_stdin_dash() {
+        // 管道输入通过标准输入转发，`-` 保持原样
+        let args = vec!["a.txt".to_string(), "-".to_string()];
+        let rewrite = AppendPolicy.rewrite_args(args.clone());
+        assert_eq!(final_args(&rewrite, &args, Mode::Enforce).unwrap(), vec!["-n", "a.txt", "-"]);
+    }
+
+    #[test]
+    fn test_default_operands() {
+        let args = vec!["-x".to_string(), "file".to_string()];
+        let operands = AppendPolicy.operands(&args);
+        assert_eq!(operands.files, vec![1]);
+        assert!(!operands.reads_stdin(&args));
+    }
+
+    #[test]
+    fn test_default_downstream_inherits() {
+        assert_eq!(AppendPolicy.downstream(), Downstream::Inherit);
+    }
+
+    fn demo_plan(action: Action, typed: &[&str]) -> Plan {
+        let typed: Vec<String> = typed.iter().map(|s| s.to_string()).collect();
+        let rewrite = AppendPolicy.rewrite_args(typed.clone());
+        Plan {
+            original: PathBuf::from("/bin/demo"),
+            mode: Mode::Strict,
+            downstream: Downstream::Capture,
+            position: Some(Position::End),
+            reads_stdin: true,
+            stdin_is_pipe: true,
+            args: rewrite.args.clone(),
+            rewrite,
+            rules_apply: true,
+            bypass: None,
+            repeats: None,
+            cache_key: None,
+            typed,
+            action,
+        }
+    }
+
+    #[test]
+    fn test_audit_record() {
+        let captured = Captured { path: "/tmp/demo_input.txt".to_string(), bytes: 12, lines: 3, sha256: None };
+        let record = audit_record(&AppendPolicy, &demo_plan(Action::Capture, &["x"]), 0, Some(captured), Local::now(), Duration::from_millis(7));
+        assert_eq!(record.argv, vec!["demo", "x"]);
+        assert_eq!(record.rewritten, Some(vec!["demo".to_string(), "-n".to_string(), "x".to_string()]));
+        assert_eq!(record.action, "capture");
+        assert_eq!(record.rules[0].rule, "demo.flag");
+        assert_eq!((record.input_bytes, record.input_lines), (Some(12), Some(3)));
+        assert_eq!(record.duration_ms, 7);
+
+        let refused = audit_record(&AppendPolicy, &demo_plan(Action::Refuse, &["x"]), 2, None, Local::now(), Duration::ZERO);
+        assert_eq!(refused.rewritten, None);
+        assert_eq!(refused.capture, None);
+    }
+
+    #[test]
+    fn test_final_args_rewrites_args() {
+        let args = vec!["foo".to_string()];
+        let rewrite = AppendPolicy.rewrite_args(args.clone());
+        assert_eq!(final_args(&rewrite, &args, Mode::Enforce).unwrap(), vec!["-n", "foo"]);
+    }
+
+    #[test]
+    fn test_warn_mode_keeps_args() {
+        let args = vec!["foo".to_string()];
+        let rewrite = AppendPolicy.rewrite_args(args.clone());
+        assert_eq!(final_args(&rewrite, &args, Mode::Warn).unwrap(), vec!["foo"]);
+        assert_eq!(final_args(&rewrite, &args, Mode::Observe).unwrap(), vec!["foo"]);
+    }
+
+    #[test]
+    fn test_strict_mode_refuses() {
+        let args = vec!["foo".to_string()];
+        let rewrite = AppendPolicy.rewrite_args(args.clone());
+        assert_eq!(final_args(&rewrite, &args, Mode::Strict), None);
+    }
+
+    #[test]
+    fn test_strict_mode_allows_sufficient_args() {
+        struct NoopPolicy;
+        impl CommandPolicy for NoopPolicy {
+            fn name(&self) -> &str {
+                "noop"
+            }
+            fn rewrite_args(&self, args: Vec<String>) -> Rewrite {
+                Rewrite::unchanged(args)
+            }
+        }
+        let args = vec!["foo".to_string()];
+        let rewrite = NoopPolicy.rewrite_args(args.clone());
+        assert_eq!(final_args(&rewrite, &args, Mode::Strict).unwrap(), vec!["foo"]);
+    }
+}
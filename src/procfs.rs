@@ -0,0 +1,336 @@
+//! 读取 Linux `/proc` 中的进程信息
+//!
+//! 根目录可以替换，测试时用临时目录模拟 `/proc`。
+//! 没有 `/proc` 的系统（macOS 等）上所有查询都返回空结果。
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// 向上查找父进程的最大层数，防止异常数据导致死循环
+const MAX_ANCESTORS: usize = 64;
+
+/// fdinfo 中 flags 的访问模式
+const O_RDONLY: u32 = 0;
+const O_WRONLY: u32 = 1;
+
+/// `/proc/<pid>/stat` 中用到的字段
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stat {
+    /// 进程名（括号中的部分）
+    pub comm: String,
+    pub ppid: u32,
+    /// 会话 id（会话首进程的 pid）
+    pub session: u32,
+    /// 进程启动时间（开机后的时钟滴答数），与 pid 一起唯一标识进程
+    pub start_time: u64,
+}
+
+/// `/proc` 文件系统
+#[derive(Debug, Clone)]
+pub struct ProcFs {
+    root: PathBuf,
+}
+
+impl ProcFs {
+    /// 以 root 作为 `/proc` 根目录
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        ProcFs { root: root.into() }
+    }
+
+    /// 系统的 `/proc`
+    pub fn system() -> Self {
+        ProcFs::new("/proc")
+    }
+
+    /// 读取 `stat`
+    pub fn stat(&self, pid: u32) -> Option<Stat> {
+        let stat = fs::read_to_string(self.root.join(pid.to_string()).join("stat")).ok()?;
+        // 进程名可能包含空格和括号，从最后一个 ')' 之后开始按空格解析
+        let open = stat.find('(')?;
+        let close = stat.rfind(')')?;
+        let fields: Vec<&str> = stat.get(close + 1..)?.split_whitespace().collect();
+        Some(Stat {
+            comm: stat.get(open + 1..close)?.to_string(),
+            ppid: fields.get(1)?.parse().ok()?,
+            session: fields.get(3)?.parse().ok()?,
+            start_time: fields.get(19)?.parse().ok()?,
+        })
+    }
+
+    /// 父进程 pid
+    pub fn parent(&self, pid: u32) -> Option<u32> {
+        self.stat(pid).map(|stat| stat.ppid)
+    }
+
+    /// 完整命令行，参数之间用空格连接；内核线程等没有命令行时返回 None
+    pub fn cmdline(&self, pid: u32) -> Option<String> {
+        self.argv(pid).map(|args| args.join(" "))
+    }
+
+    /// 命令行参数列表；没有命令行时返回 None
+    pub fn argv(&self, pid: u32) -> Option<Vec<String>> {
+        let raw = fs::read(self.root.join(pid.to_string()).join("cmdline")).ok()?;
+        let args: Vec<String> = raw
+            .split(|&b| b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+        (!args.is_empty()).then_some(args)
+    }
+
+    /// 进程名（`comm`，最长 15 个字符）
+    pub fn comm(&self, pid: u32) -> Option<String> {
+        let comm = fs::read_to_string(self.root.join(pid.to_string()).join("comm")).ok()?;
+        Some(comm.trim_end().to_string())
+    }
+
+    /// 从父进程开始，依次向上的所有祖先进程（不含 pid 0）
+    pub fn ancestors(&self, pid: u32) -> Vec<u32> {
+        let mut result = Vec::new();
+        let mut current = pid;
+        while result.len() < MAX_ANCESTORS {
+            match self.parent(current) {
+                Some(parent) if parent != 0 && parent != current => {
+                    result.push(parent);
+                    current = parent;
+                }
+                _ => break,
+            }
+        }
+        result
+    }
+
+    /// 所有进程的 pid
+    pub fn pids(&self) -> io::Result<Vec<u32>> {
+        let mut pids: Vec<u32> = fs::read_dir(&self.root)?
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .collect();
+        pids.sort_unstable();
+        Ok(pids)
+    }
+
+    /// 一次遍历找出打开了指定管道（`pipe:[inode]`）的进程
+    ///
+    /// session 不为 None 时只看该会话中的进程：同一条管道中的命令、命令替换所在的 shell
+    /// 都和当前进程在同一个会话里，不必读取其他会话的 fd。无权查看的进程会被跳过
+    pub fn pipe_ends(&self, inodes: &[u64], session: Option<u32>) -> io::Result<PipeEnds> {
+        let mut ends = PipeEnds::default();
+        if inodes.is_empty() {
+            return Ok(ends);
+        }
+        let targets: Vec<(String, u64)> = inodes.iter().map(|&inode| (format!("pipe:[{}]", inode), inode)).collect();
+        for pid in self.pids()? {
+            if session.is_some_and(|session| self.stat(pid).map(|stat| stat.session) != Some(session)) {
+                continue;
+            }
+            let dir = self.root.join(pid.to_string());
+            let Ok(fds) = fs::read_dir(dir.join("fd")) else {
+                continue;
+            };
+            for fd in fds.filter_map(Result::ok) {
+                let Ok(link) = fs::read_link(fd.path()) else {
+                    continue;
+                };
+                let Some(&(_, inode)) = targets.iter().find(|(target, _)| link.as_os_str() == target.as_str()) else {
+                    continue;
+                };
+                if let Some(access) = access_mode(&dir.join("fdinfo").join(fd.file_name())) {
+                    let end = (inode, access, pid);
+                    if !ends.ends.contains(&end) {
+                        ends.ends.push(end);
+                    }
+                }
+            }
+        }
+        Ok(ends)
+    }
+}
+
+/// 扫描得到的管道端
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipeEnds {
+    /// (管道 inode, 访问模式, pid)
+    ends: Vec<(u64, u32, u32)>,
+}
+
+impl PipeEnds {
+    /// 以只读方式打开了管道的进程（管道的下游）
+    pub fn readers(&self, inode: u64) -> Vec<u32> {
+        self.holders(inode, O_RDONLY)
+    }
+
+    /// 以只写方式打开了管道的进程（管道的上游）
+    pub fn writers(&self, inode: u64) -> Vec<u32> {
+        self.holders(inode, O_WRONLY)
+    }
+
+    fn holders(&self, inode: u64, access: u32) -> Vec<u32> {
+        self.ends
+            .iter()
+            .filter(|&&(i, a, _)| i == inode && a == access)
+            .map(|&(_, _, pid)| pid)
+            .collect()
+    }
+}
+
+/// fdinfo 中 flags 的访问模式（O_RDONLY / O_WRONLY / O_RDWR）
+fn access_mode(fdinfo: &std::path::Path) -> Option<u32> {
+    let content = fs::read_to_string(fdinfo).ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("flags:"))
+        .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok())
+        .map(|flags| flags & 0o3)
+}
+
+#[cfg(test)]
+pub(crate) mod fake {
+    //! 测试用的模拟 `/proc`
+
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::{Path, PathBuf};
+
+    pub struct FakeProc {
+        pub root: PathBuf,
+    }
+
+    impl FakeProc {
+        pub fn new(name: &str) -> Self {
+            let root = PathBuf::from("/tmp/cursor_test_procfs").join(name);
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            FakeProc { root }
+        }
+
+        /// 添加进程，会话 id 为 1
+        pub fn process(&self, pid: u32, ppid: u32, comm: &str) -> &Self {
+            self.process_in_session(pid, ppid, comm, 1)
+        }
+
+        /// 添加指定会话中的进程
+        pub fn process_in_session(&self, pid: u32, ppid: u32, comm: &str, session: u32) -> &Self {
+            let dir = self.dir(pid);
+            fs::create_dir_all(dir.join("fd")).unwrap();
+            fs::create_dir_all(dir.join("fdinfo")).unwrap();
+            // 启动时间为 1000 + pid
+            let stat = format!(
+                "{} ({}) S {} 1 {} 0 -1 4194304 0 0 0 0 0 0 0 0 20 0 1 0 {} 0 0\n",
+                pid,
+                comm,
+                ppid,
+                session,
+                1000 + pid
+            );
+            fs::write(dir.join("stat"), stat).unwrap();
+            fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+            fs::write(dir.join("cmdline"), format!("{}\0", comm)).unwrap();
+            self
+        }
+
+        /// 设置进程的命令行
+        pub fn cmdline(&self, pid: u32, args: &[&str]) -> &Self {
+            let mut raw = Vec::new();
+            for arg in args {
+                raw.extend_from_slice(arg.as_bytes());
+                raw.push(0);
+            }
+            fs::write(self.dir(pid).join("cmdline"), raw).unwrap();
+            self
+        }
+
+        /// 给进程添加一个打开的管道端，write 为 true 表示写端
+        pub fn pipe_fd(&self, pid: u32, fd: u32, inode: u64, write: bool) -> &Self {
+            let dir = self.dir(pid);
+            symlink(format!("pipe:[{}]", inode), dir.join("fd").join(fd.to_string())).unwrap();
+            let flags = if write { "01" } else { "00" };
+            fs::write(dir.join("fdinfo").join(fd.to_string()), format!("pos:\t0\nflags:\t{}\n", flags)).unwrap();
+            self
+        }
+
+        fn dir(&self, pid: u32) -> PathBuf {
+            Path::new(&self.root).join(pid.to_string())
+        }
+    }
+
+    impl Drop for FakeProc {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::FakeProc;
+    use super::*;
+
+    #[test]
+    fn test_parent_and_ancestors() {
+        let fake = FakeProc::new("ancestors");
+        fake.process(1, 0, "init").process(10, 1, "node").process(20, 10, "bash").process(30, 20, "grep");
+        let proc = ProcFs::new(&fake.root);
+        assert_eq!(proc.parent(30), Some(20));
+        assert_eq!(proc.ancestors(30), vec![20, 10, 1]);
+        assert_eq!(proc.comm(10).as_deref(), Some("node"));
+        assert_eq!(proc.parent(99), None);
+    }
+
+    #[test]
+    fn test_comm_with_spaces_and_parens() {
+        let fake = FakeProc::new("comm");
+        fake.process(5, 1, "a) b (c");
+        let stat = ProcFs::new(&fake.root).stat(5).unwrap();
+        assert_eq!(stat, Stat { comm: "a) b (c".to_string(), ppid: 1, session: 1, start_time: 1005 });
+    }
+
+    #[test]
+    fn test_cmdline() {
+        let fake = FakeProc::new("cmdline");
+        fake.process(7, 1, "node").cmdline(7, &["/opt/Cursor/cursor", "--type=agent"]);
+        let proc = ProcFs::new(&fake.root);
+        assert_eq!(proc.cmdline(7).as_deref(), Some("/opt/Cursor/cursor --type=agent"));
+        fake.cmdline(7, &[]);
+        assert_eq!(proc.cmdline(7), None);
+    }
+
+    #[test]
+    fn test_pipe_readers_ignore_write_ends() {
+        let fake = FakeProc::new("readers");
+        fake.process(20, 1, "bash")
+            .process(30, 20, "grep")
+            .pipe_fd(30, 1, 777, true)
+            .process(31, 20, "wc")
+            .pipe_fd(31, 0, 777, false)
+            .process(32, 20, "cat")
+            .pipe_fd(32, 0, 888, false);
+        let ends = ProcFs::new(&fake.root).pipe_ends(&[777, 888], None).unwrap();
+        assert_eq!(ends.readers(777), vec![31]);
+        assert_eq!(ends.writers(777), vec![30]);
+        assert_eq!(ends.readers(888), vec![32]);
+        assert!(ends.writers(888).is_empty());
+    }
+
+    #[test]
+    fn test_pipe_ends_in_session() {
+        // 其他会话中的进程不读取 fd
+        let fake = FakeProc::new("pipe_session");
+        fake.process(30, 20, "grep")
+            .pipe_fd(30, 1, 777, true)
+            .process_in_session(40, 1, "other", 2)
+            .pipe_fd(40, 0, 777, false);
+        let proc = ProcFs::new(&fake.root);
+        assert_eq!(proc.pipe_ends(&[777], None).unwrap().readers(777), vec![40]);
+        let ends = proc.pipe_ends(&[777], Some(1)).unwrap();
+        assert!(ends.readers(777).is_empty());
+        assert_eq!(ends.writers(777), vec![30]);
+    }
+
+    #[test]
+    fn test_missing_root() {
+        let proc = ProcFs::new("/tmp/cursor_test_procfs/missing");
+        assert!(proc.pipe_ends(&[1], None).is_err());
+        assert!(proc.ancestors(1).is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! 进程树检测
+//!
+//! 从父进程开始向上读取 `/proc/<pid>/stat` 和 `/proc/<pid>/cmdline`，
+//! 进程名或命令行包含任一模式（不区分大小写）即认为命令由 Agent 执行。
+//!
+//! 默认关闭：用户手动在 Cursor 终端执行命令时，祖先进程同样包含 Cursor。
+//! 适合只有 Agent 会从编辑器启动 shell 的环境，或配合更精确的命令行模式使用。
+//!
+//! 结果按会话缓存在 `sessions/<会话>/proctree` 中，同一会话的后续命令不再遍历进程树。
+
+use crate::agent::Detection;
+use crate::config::ProcessTreeConfig;
+use crate::procfs::ProcFs;
+
+use std::fs;
+use std::path::Path;
+
+/// 缓存文件名
+const CACHE_NAME: &str = "proctree";
+
+/// 沿进程树查找匹配的祖先进程
+pub fn detect(config: &ProcessTreeConfig, proc: &ProcFs, pid: u32) -> Option<Detection> {
+    let patterns: Vec<String> = config.patterns.iter().map(|p| p.to_lowercase()).collect();
+    let mut current = pid;
+    for _ in 0..config.max_depth {
+        let stat = proc.stat(current)?;
+        let parent = stat.ppid;
+        if parent == 0 || parent == current {
+            return None;
+        }
+        let parent_stat = proc.stat(parent)?;
+        let cmdline = proc.cmdline(parent).unwrap_or_default();
+        let haystacks = [parent_stat.comm.to_lowercase(), cmdline.to_lowercase()];
+        if let Some(pattern) = patterns.iter().find(|p| haystacks.iter().any(|h| h.contains(p.as_str()))) {
+            return Some(Detection {
+                agent: config.agent.clone(),
+                signal: format!("process tree: pid {} ({}) 匹配 \"{}\"", parent, parent_stat.comm, pattern),
+            });
+        }
+        current = parent;
+    }
+    None
+}
+
+/// 带会话缓存的 [`detect`]；模式或层数变化时缓存失效
+pub fn detect_cached(config: &ProcessTreeConfig, proc: &ProcFs, pid: u32, session_dir: &Path) -> Option<Detection> {
+    let cache = session_dir.join(CACHE_NAME);
+    let key = cache_key(config);
+    if let Some(cached) = read_cache(&cache, &key) {
+        return cached;
+    }
+    let result = detect(config, proc, pid);
+    // 缓存写入失败不影响检测结果
+    let _ = fs::create_dir_all(session_dir).and_then(|_| fs::write(&cache, format_cache(&key, &result)));
+    result
+}
+
+fn cache_key(config: &ProcessTreeConfig) -> String {
+    format!("{}|{}|{}", config.agent, config.max_depth, config.patterns.join("|"))
+}
+
+/// 缓存格式：第一行是配置键，第二行是 `<agent>\t<信号>`，未命中时为 `-`
+fn format_cache(key: &str, result: &Option<Detection>) -> String {
+    match result {
+        Some(found) => format!("{}\n{}\t{}\n", key, found.agent, found.signal),
+        None => format!("{}\n-\n", key),
+    }
+}
+
+/// 返回 Some(结果) 表示缓存有效
+fn read_cache(path: &Path, key: &str) -> Option<Option<Detection>> {
+    let content = fs::read_to_string(path).ok()?;
+    let mut lines = content.lines();
+    if lines.next()? != key {
+        return None;
+    }
+    match lines.next()? {
+        "-" => Some(None),
+        line => {
+            let (agent, signal) = line.split_once('\t')?;
+            Some(Some(Detection { agent: agent.to_string(), signal: format!("{}（会话缓存）", signal) }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procfs::fake::FakeProc;
+
+    fn config(patterns: &[&str], max_depth: u32) -> ProcessTreeConfig {
+        ProcessTreeConfig {
+            enabled: true,
+            agent: "cursor".to_string(),
+            max_depth,
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    /// Cursor(10) -> node(20) -> zsh(30) -> bash(40) -> grep(50)
+    fn cursor_tree(name: &str) -> FakeProc {
+        let fake = FakeProc::new(name);
+        fake.process(1, 0, "systemd")
+            .process(10, 1, "Cursor")
+            .process(20, 10, "node")
+            .cmdline(20, &["/usr/share/cursor/cursor", "--type=utility", "extensionHost"])
+            .process(30, 20, "zsh")
+            .process(40, 30, "bash")
+            .process(50, 40, "grep");
+        fake
+    }
+
+    #[test]
+    fn test_matches_cmdline() {
+        let fake = cursor_tree("proctree_cmdline");
+        let found = detect(&config(&["extensionHost"], 10), &ProcFs::new(&fake.root), 50).unwrap();
+        assert_eq!(found.agent, "cursor");
+        assert!(found.signal.contains("pid 20"));
+    }
+
+    #[test]
+    fn test_matches_comm_case_insensitive() {
+        let fake = cursor_tree("proctree_comm");
+        let found = detect(&config(&["CURSOR"], 10), &ProcFs::new(&fake.root), 20).unwrap();
+        assert!(found.signal.contains("pid 10 (Cursor)"));
+    }
+
+    #[test]
+    fn test_respects_max_depth() {
+        let fake = cursor_tree("proctree_depth");
+        let proc = ProcFs::new(&fake.root);
+        assert_eq!(detect(&config(&["Cursor"], 2), &proc, 50), None);
+        assert!(detect(&config(&["Cursor"], 3), &proc, 50).is_some());
+    }
+
+    #[test]
+    fn test_no_match() {
+        let fake = cursor_tree("proctree_none");
+        assert_eq!(detect(&config(&["windsurf"], 10), &ProcFs::new(&fake.root), 50), None);
+    }
+
+    #[test]
+    fn test_result_is_cached_per_session() {
+        let fake = cursor_tree("proctree_cache");
+        let session_dir = fake.root.join("state/sessions/1-1001");
+        let proc = ProcFs::new(&fake.root);
+        let cursor = config(&["Cursor"], 10);
+
+        let first = detect_cached(&cursor, &proc, 50, &session_dir).unwrap();
+        // 进程树消失后仍从缓存得到结果
+        fs::remove_dir_all(fake.root.join("10")).unwrap();
+        let second = detect_cached(&cursor, &proc, 50, &session_dir).unwrap();
+        assert_eq!(second.agent, first.agent);
+        assert!(second.signal.contains("会话缓存"));
+
+        // 模式变化时重新检测
+        let other = config(&["Windsurf"], 10);
+        assert_eq!(detect_cached(&other, &proc, 50, &session_dir), None);
+    }
+}
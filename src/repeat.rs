@@ -0,0 +1,228 @@
+//! 重复命令检测
+//!
+//! 包装器本身没有记忆，Agent 经常因为第一次输出不够而反复运行同一条命令。
+//! 每个会话在 `sessions/<会话>/history.jsonl` 中记录最近的调用，
+//! 同一条命令（改写后的参数、工作目录、输入都相同）在时间窗口内重复达到阈值时给出更强的提示，
+//! 指向上一次保存的文件；strict 模式下可以直接拒绝执行。
+//!
+//! 输入的标识：文件参数取路径、大小和修改时间；管道输入取保存文件元数据中的 SHA-256。
+
+use crate::config::RepeatConfig;
+use crate::lock_exclusive;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// 历史文件名（位于会话目录中）
+pub const HISTORY_NAME: &str = "history.jsonl";
+
+/// 一次调用的记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// 调用时间（Unix 秒）
+    pub time: i64,
+    /// 命令标识，见 [`key`]
+    pub key: String,
+    /// 展示用的命令行
+    pub command: String,
+    /// 保存的文件
+    pub capture: Option<String>,
+}
+
+/// 同一命令在时间窗口内的历史
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repeats {
+    /// 命令标识
+    pub key: String,
+    /// 窗口内之前的调用（从早到晚）
+    pub earlier: Vec<Entry>,
+}
+
+impl Repeats {
+    /// 包括本次在内的运行次数
+    pub fn runs(&self) -> usize {
+        self.earlier.len() + 1
+    }
+
+    /// 本次是否达到阈值
+    pub fn reached(&self, config: &RepeatConfig) -> bool {
+        config.threshold > 0 && self.runs() >= config.threshold as usize
+    }
+
+    /// 最近一次保存的文件
+    pub fn last_capture(&self) -> Option<&str> {
+        self.earlier.iter().rev().find_map(|entry| entry.capture.as_deref())
+    }
+}
+
+/// 命令标识：改写后的参数、工作目录和输入标识的 SHA-256
+pub fn key(argv: &[String], cwd: &str, input: &str) -> String {
+    let mut hasher = Sha256::new();
+    for arg in argv {
+        hasher.update(arg.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update([1]);
+    hasher.update(cwd.as_bytes());
+    hasher.update([1]);
+    hasher.update(input.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// 文件参数的输入标识：路径、大小和修改时间，文件变化后不再算作重复
+pub fn files_input(files: &[&str]) -> String {
+    let parts: Vec<String> = files
+        .iter()
+        .map(|file| {
+            let stamp = fs::metadata(file).ok().map(|meta| {
+                let modified = meta.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok());
+                format!("{}:{}", meta.len(), modified.map_or(0, |d| d.as_nanos()))
+            });
+            format!("{}={}", file, stamp.unwrap_or_else(|| "-".to_string()))
+        })
+        .collect();
+    format!("files:{}", parts.join("\n"))
+}
+
+/// 管道输入的输入标识：保存文件内容的 SHA-256（见保存文件的元数据）
+pub fn stdin_input(sha256: &str) -> String {
+    format!("stdin:{}", sha256)
+}
+
+/// 会话的调用历史
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    /// 会话目录中的历史文件
+    pub fn new(session_dir: &Path) -> Self {
+        History { path: session_dir.join(HISTORY_NAME) }
+    }
+
+    /// 查找窗口内同一命令之前的调用
+    pub fn repeats(&self, key: &str, now: i64, config: &RepeatConfig) -> Repeats {
+        let content = fs::read_to_string(&self.path).unwrap_or_default();
+        let earlier = parse(&content)
+            .into_iter()
+            .filter(|entry| entry.key == key && within(entry, now, config))
+            .collect();
+        Repeats { key: key.to_string(), earlier }
+    }
+
+    /// 追加一次调用，同时清理窗口外的记录
+    pub fn record(&self, entry: Entry, config: &RepeatConfig) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&self.path)?;
+        lock_exclusive(&file)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+
+        let now = entry.time;
+        let mut kept: Vec<Entry> = parse(&content).into_iter().filter(|e| within(e, now, config)).collect();
+        kept.push(entry);
+        let mut output = String::new();
+        for entry in &kept {
+            output.push_str(&serde_json::to_string(entry).map_err(io::Error::other)?);
+            output.push('\n');
+        }
+        file.seek(SeekFrom::Start(0))?;
+        file.set_len(0)?;
+        file.write_all(output.as_bytes())
+    }
+}
+
+fn parse(content: &str) -> Vec<Entry> {
+    content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()
+}
+
+fn within(entry: &Entry, now: i64, config: &RepeatConfig) -> bool {
+    now - entry.time <= config.window_secs as i64
+}
+
+/// 打印重复提示（统一格式），strict 模式拒绝执行时 refused 为 true
+pub fn print_repeat_message(command: &str, repeats: &Repeats, config: &RepeatConfig, refused: bool) {
+    let minutes = config.window_secs.div_ceil(60);
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    if refused {
+        eprintln!("⛔ [Cursor 强制最佳实践] 同一命令 {} 分钟内已运行 {} 次，已拒绝执行: {}", minutes, repeats.earlier.len(), command);
+    } else {
+        eprintln!("🔁 [Cursor 强制最佳实践] 同一命令 {} 分钟内第 {} 次运行: {}", minutes, repeats.runs(), command);
+    }
+    match repeats.last_capture() {
+        Some(capture) => eprintln!("📁 上次的内容保存在: {}", capture),
+        None => eprintln!("📁 输入没有变化，上次的输出仍然有效"),
+    }
+    eprintln!("💡 直接使用已有的结果，不要重复运行同一命令；需要更多内容时调整参数");
+    eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RepeatConfig {
+        RepeatConfig { enabled: true, threshold: 3, window_secs: 600, refuse: true }
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn entry(time: i64, key: &str, capture: Option<&str>) -> Entry {
+        Entry { time, key: key.to_string(), command: "tail -n 100".to_string(), capture: capture.map(String::from) }
+    }
+
+    #[test]
+    fn test_key_depends_on_argv_cwd_and_input() {
+        let base = key(&args(&["-n", "100", "a"]), "/repo", "files:a=1:2");
+        assert_eq!(base, key(&args(&["-n", "100", "a"]), "/repo", "files:a=1:2"));
+        assert_ne!(base, key(&args(&["-n", "100", "b"]), "/repo", "files:a=1:2"));
+        assert_ne!(base, key(&args(&["-n", "100", "a"]), "/other", "files:a=1:2"));
+        assert_ne!(base, key(&args(&["-n", "100", "a"]), "/repo", "files:a=1:3"));
+        // 参数边界不同不能得到相同的标识
+        assert_ne!(key(&args(&["ab", "c"]), "/", ""), key(&args(&["a", "bc"]), "/", ""));
+    }
+
+    #[test]
+    fn test_files_input_changes_with_content() {
+        let path = std::env::temp_dir().join(format!("cursor_test_repeat_{}.txt", std::process::id()));
+        fs::write(&path, "one\n").unwrap();
+        let name = path.to_str().unwrap();
+        let before = files_input(&[name]);
+        fs::write(&path, "one\ntwo\n").unwrap();
+        assert_ne!(before, files_input(&[name]));
+        let _ = fs::remove_file(&path);
+        assert!(files_input(&[name]).ends_with("=-"));
+    }
+
+    #[test]
+    fn test_history_window_and_threshold() {
+        let dir = std::env::temp_dir().join(format!("cursor_test_history_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let history = History::new(&dir);
+        let config = config();
+
+        history.record(entry(1000, "k", Some("/tmp/first.txt")), &config).unwrap();
+        history.record(entry(1100, "other", None), &config).unwrap();
+        history.record(entry(1200, "k", None), &config).unwrap();
+
+        let repeats = history.repeats("k", 1300, &config);
+        assert_eq!(repeats.runs(), 3);
+        assert!(repeats.reached(&config));
+        assert_eq!(repeats.last_capture(), Some("/tmp/first.txt"));
+
+        // 窗口外的记录不算，写入时被清理
+        assert_eq!(history.repeats("k", 1700, &config).runs(), 2);
+        history.record(entry(1700, "k", None), &config).unwrap();
+        let content = fs::read_to_string(dir.join(HISTORY_NAME)).unwrap();
+        assert_eq!(content.lines().count(), 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,160 @@
+//! 在 PATH 中查找被包装的原始命令
+//!
+//! 包装器本身通常以符号链接的形式排在 PATH 最前面，所以查找时必须跳过
+//! 指向包装器自身的条目（按规范路径或 inode 比较），否则会调用到自己。
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// 查找原始命令
+///
+/// - `names`：按顺序尝试的命令名，例如 `["grep", "ggrep"]`
+/// - `override_path`：配置中显式指定的路径，非空时直接使用
+pub fn resolve_original(names: &[&str], override_path: Option<&str>) -> io::Result<PathBuf> {
+    if let Some(path) = override_path.filter(|p| !p.is_empty()) {
+        let path = PathBuf::from(path);
+        if !is_executable(&path) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("配置指定的 {} 不存在或不可执行", path.display()),
+            ));
+        }
+        return Ok(path);
+    }
+
+    let path_var = env::var_os("PATH").unwrap_or_default();
+    let self_exe = env::current_exe().ok();
+    find_in_path(names, &path_var, self_exe.as_deref()).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("在 PATH 中找不到原始命令 {}（已跳过包装器自身）", names.join("/")),
+        )
+    })
+}
+
+/// 依次在 PATH 的每个目录中查找 names，跳过指向 self_exe 的条目
+pub fn find_in_path(names: &[&str], path_var: &OsStr, self_exe: Option<&Path>) -> Option<PathBuf> {
+    let self_id = self_exe.and_then(FileId::of);
+    for name in names {
+        for dir in env::split_paths(path_var) {
+            if dir.as_os_str().is_empty() {
+                continue;
+            }
+            let candidate = dir.join(name);
+            if !is_executable(&candidate) {
+                continue;
+            }
+            if self_id.is_some() && FileId::of(&candidate) == self_id {
+                continue;
+            }
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// 判断路径是否为可执行的普通文件（跟随符号链接）
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// 文件身份：规范路径 + (设备号, inode)，两者任一相同即视为同一文件
+#[derive(Debug)]
+struct FileId {
+    canonical: PathBuf,
+    dev_ino: (u64, u64),
+}
+
+impl FileId {
+    fn of(path: &Path) -> Option<FileId> {
+        let canonical = fs::canonicalize(path).ok()?;
+        let meta = fs::metadata(&canonical).ok()?;
+        Some(FileId { canonical, dev_ino: (meta.dev(), meta.ino()) })
+    }
+}
+
+impl PartialEq for FileId {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical == other.canonical || self.dev_ino == other.dev_ino
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    /// 在 dir 下创建一个可执行的桩脚本
+    fn write_stub(dir: &Path, name: &str) -> PathBuf {
+        fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, "#!/bin/sh\nexit 0\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_find_in_path_skips_wrapper_symlink() {
+        let root = Path::new("/tmp/cursor_test_resolve_skip");
+        let _ = fs::remove_dir_all(root);
+        let wrapper = write_stub(&root.join("wrapper"), "cursor-guard");
+        let shim_dir = root.join("shims");
+        fs::create_dir_all(&shim_dir).unwrap();
+        symlink(&wrapper, shim_dir.join("grep")).unwrap();
+        let real = write_stub(&root.join("real"), "grep");
+
+        let path_var = env::join_paths([&shim_dir, &root.join("real")]).unwrap();
+        let found = find_in_path(&["grep"], &path_var, Some(&wrapper));
+        assert_eq!(found, Some(real));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_find_in_path_skips_hardlink_to_wrapper() {
+        let root = Path::new("/tmp/cursor_test_resolve_hardlink");
+        let _ = fs::remove_dir_all(root);
+        let wrapper = write_stub(&root.join("wrapper"), "cursor-guard");
+        fs::create_dir_all(root.join("shims")).unwrap();
+        fs::hard_link(&wrapper, root.join("shims/tail")).unwrap();
+        let real = write_stub(&root.join("real"), "tail");
+
+        let path_var = env::join_paths([root.join("shims"), root.join("real")]).unwrap();
+        assert_eq!(find_in_path(&["tail"], &path_var, Some(&wrapper)), Some(real));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_find_in_path_falls_back_to_alternate_name() {
+        let root = Path::new("/tmp/cursor_test_resolve_alt");
+        let _ = fs::remove_dir_all(root);
+        let ggrep = write_stub(&root.join("brew"), "ggrep");
+        fs::write(root.join("brew/grep"), "not executable").unwrap();
+
+        let path_var = env::join_paths([root.join("brew")]).unwrap();
+        assert_eq!(find_in_path(&["grep", "ggrep"], &path_var, None), Some(ggrep));
+        assert_eq!(find_in_path(&["head"], &path_var, None), None);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_resolve_original_uses_override() {
+        let root = Path::new("/tmp/cursor_test_resolve_override");
+        let _ = fs::remove_dir_all(root);
+        let stub = write_stub(root, "my-grep");
+
+        let resolved = resolve_original(&["grep"], stub.to_str()).unwrap();
+        assert_eq!(resolved, stub);
+        assert!(resolve_original(&["grep"], Some("/nonexistent/grep")).is_err());
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
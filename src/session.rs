@@ -0,0 +1,94 @@
+//! 会话标识与状态目录
+//!
+//! Agent 的一次会话通常在同一个 shell 会话中执行多条命令。按会话保存的状态
+//! （例如进程树检测结果）放在 `~/.local/state/cursor-guard/sessions/<会话>/` 下。
+//!
+//! 会话标识优先取 `CURSOR_GUARD_SESSION`，否则用会话首进程的 pid 加启动时间，
+//! pid 被复用时不会误用旧会话的状态。
+
+use crate::procfs::ProcFs;
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+/// 显式指定会话标识的环境变量
+pub const SESSION_ENV: &str = "CURSOR_GUARD_SESSION";
+
+/// 当前进程的会话标识
+pub fn id() -> String {
+    let explicit = env::var(SESSION_ENV).ok();
+    id_from(explicit.as_deref(), &ProcFs::system(), process::id())
+}
+
+fn id_from(explicit: Option<&str>, proc: &ProcFs, pid: u32) -> String {
+    if let Some(explicit) = explicit.filter(|s| !s.is_empty()) {
+        return sanitize(explicit);
+    }
+    let leader = proc
+        .stat(pid)
+        .and_then(|stat| Some((stat.session, proc.stat(stat.session)?.start_time)));
+    match leader {
+        Some((session, start_time)) => format!("{}-{}", session, start_time),
+        // 没有 /proc 时退回到父进程（通常是 Agent 启动的 shell）
+        None => format!("ppid-{}", std::os::unix::process::parent_id()),
+    }
+}
+
+/// 会话标识用作目录名，只保留安全字符；开头的 `.` 换成 `_`，`..` 等不会跳出会话目录
+fn sanitize(raw: &str) -> String {
+    let dots = raw.len() - raw.trim_start_matches('.').len();
+    "_".repeat(dots)
+        + &raw[dots..]
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+            .collect::<String>()
+}
+
+/// 状态目录：`$XDG_STATE_HOME/cursor-guard`，默认 `~/.local/state/cursor-guard`
+pub fn state_dir() -> PathBuf {
+    let base = match env::var("XDG_STATE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").unwrap_or_else(|_| "/tmp".to_string())).join(".local/state"),
+    };
+    base.join("cursor-guard")
+}
+
+/// 当前会话的状态目录
+pub fn dir() -> PathBuf {
+    state_dir().join("sessions").join(id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procfs::fake::FakeProc;
+
+    #[test]
+    fn test_explicit_session() {
+        let proc = ProcFs::new("/tmp/cursor_test_procfs/none");
+        assert_eq!(id_from(Some("agent-42"), &proc, 1), "agent-42");
+        assert_eq!(id_from(Some("../x y"), &proc, 1), "___x_y");
+        assert_eq!(id_from(Some("v1.2"), &proc, 1), "v1.2");
+    }
+
+    #[test]
+    fn test_dot_session_stays_inside_sessions_dir() {
+        let proc = ProcFs::new("/tmp/cursor_test_procfs/none");
+        assert_eq!(id_from(Some(".."), &proc, 1), "__");
+        assert_eq!(id_from(Some("."), &proc, 1), "_");
+        assert_eq!(id_from(Some(".hidden"), &proc, 1), "_hidden");
+        for id in ["..", ".", "...", "../.."] {
+            let dir = PathBuf::from("/state/sessions").join(id_from(Some(id), &proc, 1));
+            assert_eq!(dir.parent(), Some(std::path::Path::new("/state/sessions")), "{}", id);
+            assert!(dir.file_name().is_some());
+        }
+    }
+
+    #[test]
+    fn test_session_leader_with_start_time() {
+        let fake = FakeProc::new("session");
+        fake.process(1, 0, "bash").process(30, 1, "grep");
+        assert_eq!(id_from(None, &ProcFs::new(&fake.root), 30), "1-1001");
+    }
+}
@@ -0,0 +1,549 @@
+//! `cursor-guard stats`：汇总审计日志
+//!
+//! 统计每条规则、每个命令的触发次数，请求行数低于 min_lines 的频率和请求的行数分布，
+//! 重复最多的命令以及保存的管道输入总量，用来验证阈值是否合理。
+//! 输出为终端表格（默认）、JSON 或 Markdown。
+
+use crate::explain::quote_args;
+
+use cursor_common as common;
+use common::audit::{self, Record};
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const RULE: &str = "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━";
+
+const USAGE: &str = "用法: cursor-guard stats [--format table|json|markdown] [--top N] [--log 路径]";
+
+/// 输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Table,
+    Json,
+    Markdown,
+}
+
+/// 命令行选项
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    format: Format,
+    /// 重复命令最多列出的条数
+    top: usize,
+    /// 审计日志路径，None 表示使用配置中的路径
+    log: Option<PathBuf>,
+}
+
+/// 汇总结果
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+struct Stats {
+    /// 审计日志路径
+    log: String,
+    /// 记录总数
+    invocations: u64,
+    /// 无法解析而跳过的行数
+    skipped: u64,
+    /// 按 Agent 的调用次数
+    agents: BTreeMap<String, u64>,
+    commands: Vec<CommandStats>,
+    rules: Vec<RuleStats>,
+    below_minimum: Vec<MinimumStats>,
+    repeated: Vec<Repeated>,
+    captures: CaptureStats,
+}
+
+/// 按命令统计
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+struct CommandStats {
+    command: String,
+    invocations: u64,
+    /// 至少匹配一条规则的调用次数
+    rule_hits: u64,
+    /// 绕过次数
+    bypasses: u64,
+    /// strict 模式拒绝执行的次数（参数不足或重复命令）
+    refusals: u64,
+}
+
+/// 按规则统计
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+struct RuleStats {
+    rule: String,
+    /// 匹配次数
+    hits: u64,
+    /// 其中规则生效的次数（不含绕过、输出接到下游程序）
+    applied: u64,
+}
+
+/// 请求行数低于 min_lines 的统计
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+struct MinimumStats {
+    rule: String,
+    hits: u64,
+    /// 该命令的调用总数
+    invocations: u64,
+    /// 请求的行数分布，按次数从多到少
+    requested: Vec<Requested>,
+}
+
+/// 请求某个行数的次数
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Requested {
+    /// 请求的行数，None 表示没有指定
+    lines: Option<u64>,
+    count: u64,
+}
+
+/// 重复执行的命令
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Repeated {
+    command_line: String,
+    count: u64,
+}
+
+/// 管道输入保存统计
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+struct CaptureStats {
+    files: u64,
+    bytes: u64,
+    lines: u64,
+}
+
+/// stats 入口
+pub fn run(args: Vec<String>) -> i32 {
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("❌ stats: {}", e);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    let path = options
+        .log
+        .clone()
+        .unwrap_or_else(|| audit::log_path(&common::config::global().audit));
+    let content = match read_log(&path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("❌ stats: 无法读取审计日志 {}: {}", path.display(), e);
+            return 1;
+        }
+    };
+
+    let mut stats = aggregate(&content, options.top);
+    stats.log = path.display().to_string();
+    match options.format {
+        Format::Table => print!("{}", render_table(&stats)),
+        Format::Markdown => print!("{}", render_markdown(&stats)),
+        Format::Json => match serde_json::to_string_pretty(&stats) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("❌ stats: {}", e);
+                return 1;
+            }
+        },
+    }
+    0
+}
+
+/// 读取审计日志：先读轮转出的 `<path>.1`（存在时），再读当前日志
+fn read_log(path: &Path) -> io::Result<String> {
+    let mut content = match fs::read_to_string(audit::rotated_path(path)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    match fs::read_to_string(path) {
+        Ok(current) => content.push_str(&current),
+        // 刚轮转、还没有新记录时当前日志不存在
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !content.is_empty() => {}
+        Err(e) => return Err(e),
+    }
+    Ok(content)
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options { format: Format::Table, top: 10, log: None };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || inline.clone().or_else(|| iter.next().cloned()).ok_or(format!("{} 需要参数", flag));
+        match flag {
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "table" => Format::Table,
+                    "json" => Format::Json,
+                    "markdown" | "md" => Format::Markdown,
+                    other => return Err(format!("未知的输出格式 '{}'", other)),
+                }
+            }
+            "--top" => {
+                let raw = value()?;
+                options.top = raw.parse().map_err(|_| format!("--top 的值无效: {}", raw))?;
+            }
+            "--log" => options.log = Some(PathBuf::from(value()?)),
+            other => return Err(format!("未知的选项 '{}'", other)),
+        }
+    }
+    Ok(options)
+}
+
+/// 汇总审计日志内容
+fn aggregate(content: &str, top: usize) -> Stats {
+    let mut stats = Stats::default();
+    let mut commands: BTreeMap<String, CommandStats> = BTreeMap::new();
+    let mut rules: BTreeMap<String, RuleStats> = BTreeMap::new();
+    let mut requested: BTreeMap<String, BTreeMap<Option<u64>, u64>> = BTreeMap::new();
+    let mut repeated: BTreeMap<String, u64> = BTreeMap::new();
+
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let Ok(record) = serde_json::from_str::<Record>(line) else {
+            stats.skipped += 1;
+            continue;
+        };
+        stats.invocations += 1;
+        *stats.agents.entry(record.agent.clone().unwrap_or_else(|| "-".to_string())).or_default() += 1;
+
+        let command = commands.entry(record.command.clone()).or_insert_with(|| CommandStats {
+            command: record.command.clone(),
+            ..CommandStats::default()
+        });
+        command.invocations += 1;
+        command.rule_hits += u64::from(!record.rules.is_empty());
+        command.bypasses += u64::from(record.bypass.is_some());
+        command.refusals += u64::from(record.action.starts_with("refuse"));
+
+        for enforcement in &record.rules {
+            let rule = rules.entry(enforcement.rule.clone()).or_insert_with(|| RuleStats {
+                rule: enforcement.rule.clone(),
+                ..RuleStats::default()
+            });
+            rule.hits += 1;
+            rule.applied += u64::from(record.rules_applied);
+            if enforcement.rule.ends_with(".min_lines") {
+                let counts = requested.entry(enforcement.rule.clone()).or_default();
+                *counts.entry(requested_count(&enforcement.original)).or_default() += 1;
+            }
+        }
+
+        *repeated.entry(quote_args(&record.argv)).or_default() += 1;
+        if let Some(bytes) = record.input_bytes {
+            stats.captures.files += 1;
+            stats.captures.bytes += bytes;
+            stats.captures.lines += record.input_lines.unwrap_or(0);
+        }
+    }
+
+    stats.below_minimum = requested
+        .into_iter()
+        .map(|(rule, counts)| {
+            let command = rule.split('.').next().unwrap_or_default();
+            let mut requested: Vec<Requested> =
+                counts.into_iter().map(|(lines, count)| Requested { lines, count }).collect();
+            requested.sort_by(|a, b| b.count.cmp(&a.count).then(a.lines.cmp(&b.lines)));
+            MinimumStats {
+                hits: requested.iter().map(|r| r.count).sum(),
+                invocations: commands.get(command).map_or(0, |c| c.invocations),
+                rule,
+                requested,
+            }
+        })
+        .collect();
+    stats.commands = commands.into_values().collect();
+    stats.rules = rules.into_values().collect();
+    let mut repeated: Vec<Repeated> = repeated
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(command_line, count)| Repeated { command_line, count })
+        .collect();
+    repeated.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.command_line.cmp(&b.command_line)));
+    repeated.truncate(top);
+    stats.repeated = repeated;
+    stats
+}
+
+/// 从规则的原始写法中取出请求的数量：`tail -n 5` → 5，`head -qn5` → 5，`head` → None
+fn requested_count(original: &str) -> Option<u64> {
+    let mut tokens = original.split_whitespace();
+    tokens.next();
+    let last = tokens.last()?;
+    let digits: String = last
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    digits.parse().ok()
+}
+
+fn describe_requested(requested: &[Requested]) -> String {
+    let parts: Vec<String> = requested
+        .iter()
+        .map(|r| match r.lines {
+            Some(lines) => format!("{} ×{}", lines, r.count),
+            None => format!("未指定 ×{}", r.count),
+        })
+        .collect();
+    parts.join(", ")
+}
+
+fn percent(part: u64, total: u64) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!("{:.0}%", part as f64 * 100.0 / total as f64)
+}
+
+/// 表格形式的一节：标题、表头和数据行
+struct Section {
+    title: &'static str,
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+fn sections(stats: &Stats) -> Vec<Section> {
+    vec![
+        Section {
+            title: "🤖 按 Agent",
+            headers: vec!["Agent", "调用"],
+            rows: stats.agents.iter().map(|(agent, count)| vec![agent.clone(), count.to_string()]).collect(),
+        },
+        Section {
+            title: "📋 按命令",
+            headers: vec!["命令", "调用", "规则触发", "绕过", "拒绝"],
+            rows: stats
+                .commands
+                .iter()
+                .map(|c| {
+                    vec![
+                        c.command.clone(),
+                        c.invocations.to_string(),
+                        format!("{}（{}）", c.rule_hits, percent(c.rule_hits, c.invocations)),
+                        c.bypasses.to_string(),
+                        c.refusals.to_string(),
+                    ]
+                })
+                .collect(),
+        },
+        Section {
+            title: "📏 按规则",
+            headers: vec!["规则", "匹配", "生效"],
+            rows: stats
+                .rules
+                .iter()
+                .map(|r| vec![r.rule.clone(), r.hits.to_string(), r.applied.to_string()])
+                .collect(),
+        },
+        Section {
+            title: "📉 请求行数低于 min_lines",
+            headers: vec!["规则", "次数", "占该命令调用", "请求的行数"],
+            rows: stats
+                .below_minimum
+                .iter()
+                .map(|m| {
+                    vec![
+                        m.rule.clone(),
+                        m.hits.to_string(),
+                        percent(m.hits, m.invocations),
+                        describe_requested(&m.requested),
+                    ]
+                })
+                .collect(),
+        },
+        Section {
+            title: "🔁 重复最多的命令",
+            headers: vec!["次数", "命令"],
+            rows: stats
+                .repeated
+                .iter()
+                .map(|r| vec![r.count.to_string(), r.command_line.clone()])
+                .collect(),
+        },
+    ]
+}
+
+fn summary_lines(stats: &Stats) -> Vec<String> {
+    let mut lines = vec![format!("💾 保存的输入: {} 个文件，共 {} 行（{} 字节）", stats.captures.files, stats.captures.lines, stats.captures.bytes)];
+    if stats.skipped > 0 {
+        lines.push(format!("⚠️  跳过 {} 行无法解析的记录", stats.skipped));
+    }
+    lines
+}
+
+/// 终端宽度：中日韩字符和全角符号占两列
+pub(crate) fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| if matches!(c as u32, 0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFF00..=0xFF60) { 2 } else { 1 })
+        .sum()
+}
+
+pub(crate) fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(display_width(text))))
+}
+
+fn render_table(stats: &Stats) -> String {
+    let mut out = format!("📊 cursor-guard stats: {} 次调用（{}）\n{}\n", stats.invocations, stats.log, RULE);
+    for section in sections(stats) {
+        out.push_str(&format!("{}\n", section.title));
+        if section.rows.is_empty() {
+            out.push_str("   （无）\n");
+            continue;
+        }
+        let widths: Vec<usize> = (0..section.headers.len())
+            .map(|i| {
+                section
+                    .rows
+                    .iter()
+                    .map(|row| display_width(&row[i]))
+                    .chain(std::iter::once(display_width(section.headers[i])))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let header: Vec<String> = section.headers.iter().zip(&widths).map(|(h, w)| pad(h, *w)).collect();
+        out.push_str(&format!("   {}\n", header.join("  ").trim_end()));
+        for row in &section.rows {
+            let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, w)| pad(cell, *w)).collect();
+            out.push_str(&format!("   {}\n", cells.join("  ").trim_end()));
+        }
+    }
+    for line in summary_lines(stats) {
+        out.push_str(&format!("{}\n", line));
+    }
+    out.push_str(&format!("{}\n", RULE));
+    out
+}
+
+fn render_markdown(stats: &Stats) -> String {
+    let mut out = format!("# cursor-guard stats\n\n共 {} 次调用（`{}`）\n", stats.invocations, stats.log);
+    for section in sections(stats) {
+        out.push_str(&format!("\n## {}\n\n", section.title));
+        if section.rows.is_empty() {
+            out.push_str("（无）\n");
+            continue;
+        }
+        out.push_str(&format!("| {} |\n", section.headers.join(" | ")));
+        out.push_str(&format!("|{}\n", "------|".repeat(section.headers.len())));
+        for row in &section.rows {
+            let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            out.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+    }
+    out.push('\n');
+    for line in summary_lines(stats) {
+        out.push_str(&format!("{}\n", line));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(command: &str, argv: &[&str], rules: &[(&str, &str)], extra: &str) -> String {
+        let argv: Vec<String> = argv.iter().map(|a| format!("\"{}\"", a)).collect();
+        let rules: Vec<String> = rules
+            .iter()
+            .map(|(rule, original)| format!(r#"{{"rule":"{}","original":"{}","enforced":"-"}}"#, rule, original))
+            .collect();
+        format!(
+            r#"{{"timestamp":"t","session":"s","cwd":"/","agent":"cursor","command":"{}","mode":"enforce","action":"run","bypass":null,"argv":[{}],"rewritten":null,"rules":[{}],"rules_applied":true,"capture":null,"input_bytes":null,"input_lines":null,"exit_code":0,"duration_ms":1{}}}"#,
+            command,
+            argv.join(","),
+            rules.join(","),
+            extra
+        )
+    }
+
+    fn sample() -> String {
+        [
+            line("tail", &["tail", "-n", "5", "a.log"], &[("tail.min_lines", "tail -n 5")], ""),
+            line("tail", &["tail", "-n", "5", "a.log"], &[("tail.min_lines", "tail -n 5")], ""),
+            line("tail", &["tail", "-n", "500", "a.log"], &[], ""),
+            line("head", &["head", "x"], &[("head.min_lines", "head")], ""),
+            line("grep", &["grep", "err"], &[("grep.context", "grep")], r#","input_bytes":100,"input_lines":4"#)
+                .replace(r#""input_bytes":null,"input_lines":null,"#, ""),
+            "not json".to_string(),
+        ]
+        .join("\n")
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let stats = aggregate(&sample(), 10);
+        assert_eq!(stats.invocations, 5);
+        assert_eq!(stats.skipped, 1);
+        let tail = stats.commands.iter().find(|c| c.command == "tail").unwrap();
+        assert_eq!((tail.invocations, tail.rule_hits), (3, 2));
+        let tail_min = stats.below_minimum.iter().find(|m| m.rule == "tail.min_lines").unwrap();
+        assert_eq!((tail_min.hits, tail_min.invocations), (2, 3));
+        assert_eq!(tail_min.requested, vec![Requested { lines: Some(5), count: 2 }]);
+        assert_eq!(stats.repeated, vec![Repeated { command_line: "tail -n 5 a.log".to_string(), count: 2 }]);
+        assert_eq!(stats.captures, CaptureStats { files: 1, bytes: 100, lines: 4 });
+    }
+
+    #[test]
+    fn test_read_log_includes_rotated() {
+        let path = std::env::temp_dir().join(format!("cursor_test_stats_{}.jsonl", std::process::id()));
+        let rotated = audit::rotated_path(&path);
+        let old = line("tail", &["tail", "-n", "5", "a.log"], &[("tail.min_lines", "tail -n 5")], "");
+        let new = line("head", &["head", "x"], &[], "");
+        fs::write(&rotated, format!("{}\n", old)).unwrap();
+        assert!(read_log(&path).is_ok(), "刚轮转时只有 .1 也能读取");
+        fs::write(&path, format!("{}\n", new)).unwrap();
+
+        let content = read_log(&path).unwrap();
+        assert_eq!(content, format!("{}\n{}\n", old, new));
+        let stats = aggregate(&content, 10);
+        assert_eq!(stats.invocations, 2);
+        assert!(stats.commands.iter().any(|c| c.command == "tail"));
+
+        fs::remove_file(&rotated).unwrap();
+        assert_eq!(read_log(&path).unwrap(), format!("{}\n", new));
+        fs::remove_file(&path).unwrap();
+        assert!(read_log(&path).is_err());
+    }
+
+    #[test]
+    fn test_requested_count() {
+        assert_eq!(requested_count("tail -n 5"), Some(5));
+        assert_eq!(requested_count("head -qn5"), Some(5));
+        assert_eq!(requested_count("head --lines=3"), Some(3));
+        assert_eq!(requested_count("head"), None);
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args: Vec<String> = ["--format=json", "--top", "3"].iter().map(|s| s.to_string()).collect();
+        let options = parse_options(&args).unwrap();
+        assert_eq!((options.format, options.top), (Format::Json, 3));
+        assert!(parse_options(&["--format".to_string(), "xml".to_string()]).is_err());
+        assert!(parse_options(&["--top".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_renderers() {
+        let stats = aggregate(&sample(), 10);
+        let table = render_table(&stats);
+        assert!(table.contains("tail.min_lines  2     67%"));
+        let markdown = render_markdown(&stats);
+        assert!(markdown.contains("| 规则 | 匹配 | 生效 |"));
+        assert!(markdown.contains("| tail.min_lines | 2 | 2 |"));
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("规则"), 4);
+        assert_eq!(display_width("tail"), 4);
+        assert_eq!(pad("命令", 6), "命令  ");
+    }
+}
@@ -7,6 +7,7 @@
 set -e
 
 INSTALL_DIR="/usr/local/bin"
+MULTICALL="cursor-guard"
 BINARIES=("grep" "tail" "head")
 
 echo "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"
@@ -24,8 +25,8 @@ fi
 
 # 删除包装器
 echo "🗑️  删除包装器..."
-for bin in "${BINARIES[@]}"; do
-    if [ -f "$INSTALL_DIR/$bin" ]; then
+for bin in "${BINARIES[@]}" "$MULTICALL"; do
+    if [ -e "$INSTALL_DIR/$bin" ] || [ -L "$INSTALL_DIR/$bin" ]; then
         $SUDO rm -f "$INSTALL_DIR/$bin"
         echo "  ✅ 已删除 $INSTALL_DIR/$bin"
     else